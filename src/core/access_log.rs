@@ -0,0 +1,286 @@
+use super::introspect::json_string;
+use super::{Middleware, Next, Params, Reply, Req, boxed, remote_addr, request_id};
+use fluxio::body::HttpBody;
+use fluxio::header;
+use fluxio::{Body, Response};
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use styledlog::Colorize;
+use wtime::calc::{calc_date, get_month_name};
+use wtime::utc::utc_ts_sec;
+
+/// The format of access log lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Common Log Format: `127.0.0.1 - - [18/Oct/2026:07:31:38 +0000] "GET / HTTP/1.1" 200 6`
+    Common,
+    /// Combined Log Format: the Common Log Format followed by the referer and user agent
+    /// (and the quoted request ID, if enabled).
+    Combined,
+    /// Colored, compact output for development: `GET / 200 0.42 ms 6 B`
+    Dev,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Where access log lines are written.
+enum LogOutput {
+    Stdout,                        // Standard output
+    File(Mutex<LineWriter<File>>), // A file opened in append mode
+}
+
+/// Access logging middleware, writing one line per request.
+///
+/// Each line records the client IP, method, path, status, response size, latency and
+/// request ID (see `Fluxor::set_request_id`).
+/// The response size is taken from `Content-Length` or the body size, and logged as `-`
+/// when unknown (e.g. streamed bodies). Add it with `Fluxor::wrap` to log every request,
+/// including static files and 404s.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use fluxor::prelude::*;
+///
+/// let mut app = Fluxor::new();
+/// app.wrap(AccessLog::new(LogFormat::Dev));
+/// app.wrap(AccessLog::new(LogFormat::Combined).output_file("access.log").unwrap());
+/// ```
+#[derive(Clone)]
+pub struct AccessLog {
+    format: LogFormat,      // Format of the log lines
+    output: Arc<LogOutput>, // Destination of the log lines
+}
+
+/// The recorded details of a request.
+struct Entry {
+    remote_addr: String,        // Client IP address, or "-"
+    method: String,             // Request method
+    target: String,             // Request path and query
+    version: String,            // HTTP version, e.g. "HTTP/1.1"
+    referer: Option<String>,    // Referer header
+    user_agent: Option<String>, // User-Agent header
+    status: u16,                // Response status code
+    bytes: Option<u64>,         // Response body size, if known
+    latency: Duration,          // Time until the response was ready
+    time: u64,                  // Request time, in seconds since the UNIX epoch
+    request_id: Option<String>, // Request ID, if enabled
+}
+
+impl AccessLog {
+    /// Creates an access log writing to standard output.
+    ///
+    /// # Arguments
+    ///
+    /// * `format`: The format of the log lines.
+    ///
+    /// # Returns
+    ///
+    /// A new AccessLog instance.
+    pub fn new(format: LogFormat) -> Self {
+        Self {
+            format,
+            output: Arc::new(LogOutput::Stdout),
+        }
+    }
+
+    /// Writes the log lines to a file instead of standard output, appending to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path of the log file, created if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// The access log, or the error raised while opening the file.
+    pub fn output_file<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.output = Arc::new(LogOutput::File(Mutex::new(LineWriter::new(file))));
+        Ok(self)
+    }
+
+    /// Formats a log line.
+    fn format(&self, entry: &Entry) -> String {
+        let bytes = entry
+            .bytes
+            .map_or("-".to_string(), |bytes| bytes.to_string());
+        match self.format {
+            LogFormat::Common => format!(
+                r#"{} - - [{}] "{} {} {}" {} {}"#,
+                entry.remote_addr,
+                clf_time(entry.time),
+                entry.method,
+                entry.target,
+                entry.version,
+                entry.status,
+                bytes
+            ),
+            LogFormat::Combined => format!(
+                r#"{} - - [{}] "{} {} {}" {} {} "{}" "{}"{}"#,
+                entry.remote_addr,
+                clf_time(entry.time),
+                entry.method,
+                entry.target,
+                entry.version,
+                entry.status,
+                bytes,
+                entry.referer.as_deref().unwrap_or("-").replace('"', "\\\""),
+                entry
+                    .user_agent
+                    .as_deref()
+                    .unwrap_or("-")
+                    .replace('"', "\\\""),
+                entry
+                    .request_id
+                    .as_ref()
+                    .map_or(String::new(), |id| format!(r#" "{}""#, id))
+            ),
+            LogFormat::Dev => {
+                let status = entry.status.to_string();
+                let status = match entry.status {
+                    500.. => status.red(),
+                    400..=499 => status.yellow(),
+                    300..=399 => status.cyan(),
+                    _ => status.green(),
+                };
+                format!(
+                    "{} {} {} {} {:.2} ms {}{}",
+                    "[HTTP]".cyan(),
+                    entry.method.magenta(),
+                    entry.target,
+                    status,
+                    entry.latency.as_secs_f64() * 1000.0,
+                    entry
+                        .bytes
+                        .map_or("-".to_string(), |bytes| format!("{} B", bytes))
+                        .dimmed(),
+                    entry
+                        .request_id
+                        .as_ref()
+                        .map_or(String::new(), |id| format!(" {}", id))
+                        .dimmed()
+                )
+            }
+            LogFormat::Json => format!(
+                r#"{{"time": {}, "remote_addr": {}, "method": {}, "target": {}, "version": {}, "status": {}, "bytes": {}, "latency_ms": {:.3}, "referer": {}, "user_agent": {}, "request_id": {}}}"#,
+                json_string(&iso_time(entry.time)),
+                json_string(&entry.remote_addr),
+                json_string(&entry.method),
+                json_string(&entry.target),
+                json_string(&entry.version),
+                entry.status,
+                entry
+                    .bytes
+                    .map_or("null".to_string(), |bytes| bytes.to_string()),
+                entry.latency.as_secs_f64() * 1000.0,
+                entry
+                    .referer
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+                entry
+                    .user_agent
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+                entry
+                    .request_id
+                    .as_deref()
+                    .map_or("null".to_string(), json_string)
+            ),
+        }
+    }
+}
+
+impl LogOutput {
+    /// Writes a log line, ignoring write errors so logging never fails a request.
+    fn write(&self, line: &str) {
+        match self {
+            LogOutput::Stdout => println!("{}", line),
+            LogOutput::File(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", line);
+                }
+            }
+        }
+    }
+}
+
+impl Middleware for AccessLog {
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply {
+        let start = Instant::now();
+        let header_value = |name: header::HeaderName| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let mut entry = Entry {
+            remote_addr: remote_addr(&req).map_or("-".to_string(), |addr| addr.ip().to_string()),
+            method: req.method().to_string(),
+            target: req
+                .uri()
+                .path_and_query()
+                .map_or("/".to_string(), |target| target.to_string()),
+            version: format!("{:?}", req.version()),
+            referer: header_value(header::REFERER),
+            user_agent: header_value(header::USER_AGENT),
+            status: 0,
+            bytes: None,
+            latency: Duration::ZERO,
+            time: utc_ts_sec(),
+            request_id: request_id(&req).map(str::to_string),
+        };
+
+        let log = self.clone();
+        boxed(async move {
+            let response = next.run(req, params).await?;
+            entry.status = response.status().as_u16();
+            entry.bytes = response_size(&response);
+            entry.latency = start.elapsed();
+            log.output.write(&log.format(&entry));
+            Ok(response)
+        })
+    }
+}
+
+/// Returns the size of a response body, from `Content-Length` or the body itself.
+fn response_size(response: &Response<Body>) -> Option<u64> {
+    response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .or_else(|| response.body().size_hint().exact())
+}
+
+/// Formats a UNIX timestamp in the Common Log Format, e.g. `18/Oct/2026:07:31:38 +0000`.
+fn clf_time(time: u64) -> String {
+    let (year, month, day) = calc_date(time);
+    let seconds = time % 86_400;
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        &get_month_name(month)[..3],
+        year,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Formats a UNIX timestamp in ISO 8601, e.g. `2026-10-18T07:31:38Z`.
+fn iso_time(time: u64) -> String {
+    let (year, month, day) = calc_date(time);
+    let seconds = time % 86_400;
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
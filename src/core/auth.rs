@@ -0,0 +1,482 @@
+use super::{Middleware, Next, Params, Reply, Req, boxed, error_response};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use fluxio::StatusCode;
+use fluxio::header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::num::NonZeroU32;
+use std::panic;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Checks a user name and password (see `BasicAuth::new`).
+pub type CredentialsClosure = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// Verifies a bearer token, returning the name of its principal (see `BearerAuth::new`).
+pub type TokenClosure = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// PBKDF2 iterations used by `BasicAuth::hash_password`.
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// The authenticated user of a request, stored in the request extensions by
+/// `BasicAuth` and `BearerAuth` (see `principal`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,         // User name, or the name returned by the token verifier
+    pub scheme: &'static str, // Authentication scheme, "Basic" or "Bearer"
+}
+
+/// Returns the authenticated user of a request.
+///
+/// # Arguments
+///
+/// * `req`: The request.
+///
+/// # Returns
+///
+/// The principal set by an authentication middleware, or `None` if the request did not
+/// go through one.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn dashboard(req: Req, _params: Params) -> Reply {
+///     let name = principal(&req).map_or("anonymous", |principal| principal.name.as_str());
+///     let body = format!("Welcome, {}!", name);
+///     boxed(async move { Ok(Response::new(Body::from(body))) })
+/// }
+/// ```
+pub fn principal(req: &Req) -> Option<&Principal> {
+    req.extensions().get::<Principal>()
+}
+
+/// HTTP Basic authentication middleware.
+///
+/// Requests without valid credentials get `401 Unauthorized` with a
+/// `WWW-Authenticate: Basic` challenge; authenticated requests carry a `Principal`.
+/// Credentials are checked with a closure or against a users file (see `from_users_file`).
+/// Attach it to a group with `Fluxor::wrap` or to a route with `Route::wrap`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn stats(_req: Req, _params: Params) -> Reply {
+///     boxed(async { Ok(Response::new(Body::from("stats"))) })
+/// }
+///
+/// let mut app = Fluxor::new();
+/// app.group("/admin", |admin| {
+///     admin.wrap(BasicAuth::new("Admin", |user, password| {
+///         user == "admin" && password == "s3cret"
+///     }));
+///     admin.route(GET, "/stats", stats);
+/// });
+/// ```
+#[derive(Clone)]
+pub struct BasicAuth {
+    realm: String,             // Realm sent in the challenge
+    check: CredentialsClosure, // Checks a user name and password
+}
+
+/// A password hash of a users file.
+struct PasswordHash {
+    iterations: NonZeroU32, // PBKDF2 iterations
+    salt: Vec<u8>,          // Random salt
+    hash: Vec<u8>,          // PBKDF2-HMAC-SHA256 of the password
+}
+
+impl BasicAuth {
+    /// Creates a Basic authentication middleware checking credentials with a closure.
+    ///
+    /// The closure runs on the blocking thread pool, so it may hash passwords.
+    ///
+    /// # Arguments
+    ///
+    /// * `realm`: The realm sent in the challenge (e.g. "Admin").
+    /// * `check`: A closure taking the user name and password, returning whether they are valid.
+    ///
+    /// # Returns
+    ///
+    /// A new BasicAuth instance.
+    pub fn new<F>(realm: &str, check: F) -> Self
+    where
+        F: Fn(&str, &str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            realm: realm.to_string(),
+            check: Arc::new(check),
+        }
+    }
+
+    /// Creates a Basic authentication middleware checking credentials against a users file.
+    ///
+    /// Each line of the file holds a user name and a password hash generated with
+    /// `hash_password`, separated by a colon. Blank lines and lines starting with `#`
+    /// are ignored.
+    ///
+    /// ```text
+    /// # users.txt
+    /// admin:$pbkdf2-sha256$100000$dZ9xH2Em0TJgL6eeaCatSw$fuWkLssPoZe4coaxVvEMTp51Zm894KI58bDPkwI22wg
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `realm`: The realm sent in the challenge.
+    /// * `path`: The path of the users file.
+    ///
+    /// # Returns
+    ///
+    /// The middleware, or an error if the file cannot be read or a line is invalid.
+    pub fn from_users_file<P: AsRef<Path>>(realm: &str, path: P) -> io::Result<Self> {
+        let mut users = HashMap::new();
+        let mut iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid users file entry on line {}", number + 1),
+                )
+            };
+            let (user, hash) = line.split_once(':').ok_or_else(invalid)?;
+            let hash = parse_hash(hash).ok_or_else(invalid)?;
+            iterations = iterations.max(hash.iterations);
+            users.insert(user.to_string(), hash);
+        }
+
+        // Unknown users are checked against a hash that never matches, costing as much as
+        // the slowest real one, so response times do not reveal which users exist
+        let unknown = PasswordHash {
+            iterations,
+            salt: random_salt().to_vec(),
+            hash: vec![0; 32],
+        };
+        Ok(Self::new(realm, move |user, password| {
+            let (stored, known) = match users.get(user) {
+                Some(stored) => (stored, true),
+                None => (&unknown, false),
+            };
+            let verified = pbkdf2::verify(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                stored.iterations,
+                &stored.salt,
+                password.as_bytes(),
+                &stored.hash,
+            )
+            .is_ok();
+            verified && known
+        }))
+    }
+
+    /// Hashes a password for a users file, with PBKDF2-HMAC-SHA256 and a random salt.
+    ///
+    /// # Arguments
+    ///
+    /// * `password`: The password.
+    ///
+    /// # Returns
+    ///
+    /// A hash such as `$pbkdf2-sha256$100000$<salt>$<hash>`, to write after `user:`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fluxor::prelude::*;
+    ///
+    /// let line = format!("admin:{}", BasicAuth::hash_password("s3cret"));
+    /// assert!(line.starts_with("admin:$pbkdf2-sha256$"));
+    /// ```
+    pub fn hash_password(password: &str) -> String {
+        let salt = random_salt();
+        let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
+        let mut hash = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations,
+            &salt,
+            password.as_bytes(),
+            &mut hash,
+        );
+        format!(
+            "$pbkdf2-sha256${}${}${}",
+            iterations,
+            STANDARD_NO_PAD.encode(salt),
+            STANDARD_NO_PAD.encode(hash)
+        )
+    }
+
+    /// Decodes the credentials of a `Basic` authorization header.
+    fn credentials(req: &Req) -> Option<(String, String)> {
+        let encoded = scheme_value(req, "Basic")?;
+        let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        Some((user.to_string(), password.to_string()))
+    }
+}
+
+impl Middleware for BasicAuth {
+    fn handle(&self, mut req: Req, params: Params, next: Next) -> Reply {
+        let challenge = format!(r#"Basic realm="{}", charset="UTF-8""#, quote(&self.realm));
+        let Some((user, password)) = Self::credentials(&req) else {
+            return unauthorized(&req, challenge);
+        };
+        let check = self.check.clone();
+
+        boxed(async move {
+            // Password hashing is CPU-bound, keep it off the async runtime
+            let name = user.clone();
+            let valid = match tokio::task::spawn_blocking(move || check(&name, &password)).await {
+                Ok(valid) => valid,
+                Err(error) if error.is_panic() => panic::resume_unwind(error.into_panic()),
+                Err(_) => false,
+            };
+            if !valid {
+                return unauthorized(&req, challenge).await;
+            }
+            req.extensions_mut().insert(Principal {
+                name: user,
+                scheme: "Basic",
+            });
+            next.run(req, params).await
+        })
+    }
+}
+
+/// HTTP Bearer token authentication middleware.
+///
+/// Requests without a valid token get `401 Unauthorized` with a `WWW-Authenticate: Bearer`
+/// challenge; authenticated requests carry a `Principal` named by the token verifier.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn metrics(_req: Req, _params: Params) -> Reply {
+///     boxed(async { Ok(Response::new(Body::from("metrics"))) })
+/// }
+///
+/// let mut app = Fluxor::new();
+/// app.route(GET, "/metrics", metrics).wrap(BearerAuth::new("Metrics", |token| {
+///     (token == "scraper-token").then(|| "prometheus".to_string())
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct BearerAuth {
+    realm: String,        // Realm sent in the challenge
+    verify: TokenClosure, // Verifies a token
+}
+
+impl BearerAuth {
+    /// Creates a Bearer authentication middleware verifying tokens with a closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `realm`: The realm sent in the challenge.
+    /// * `verify`: A closure taking the token and returning the name of its principal,
+    ///   or `None` if the token is invalid.
+    ///
+    /// # Returns
+    ///
+    /// A new BearerAuth instance.
+    pub fn new<F>(realm: &str, verify: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            realm: realm.to_string(),
+            verify: Arc::new(verify),
+        }
+    }
+}
+
+impl Middleware for BearerAuth {
+    fn handle(&self, mut req: Req, params: Params, next: Next) -> Reply {
+        let token = scheme_value(&req, "Bearer").map(str::to_string);
+        match token.as_deref().and_then(|token| (self.verify)(token)) {
+            Some(name) => {
+                req.extensions_mut().insert(Principal {
+                    name,
+                    scheme: "Bearer",
+                });
+                next.run(req, params)
+            }
+            None => {
+                let mut challenge = format!(r#"Bearer realm="{}""#, quote(&self.realm));
+                if token.is_some() {
+                    challenge.push_str(r#", error="invalid_token""#);
+                }
+                unauthorized(&req, challenge)
+            }
+        }
+    }
+}
+
+/// Returns the credentials of an `Authorization` header using the given scheme.
+pub(crate) fn scheme_value<'r>(req: &'r Req, scheme: &str) -> Option<&'r str> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (name, credentials) = value.split_once(' ')?;
+    name.eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
+        .filter(|credentials| !credentials.is_empty())
+}
+
+/// Builds a 401 Unauthorized response with a `WWW-Authenticate` challenge.
+pub(crate) fn unauthorized(req: &Req, challenge: String) -> Reply {
+    let mut response = error_response(req, StatusCode::UNAUTHORIZED, None);
+    if let Ok(challenge) = HeaderValue::from_str(&challenge) {
+        response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+    }
+    boxed(async move { Ok(response) })
+}
+
+/// Escapes a value for a quoted string of a challenge.
+pub(crate) fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Generates a random 16-byte salt.
+fn random_salt() -> [u8; 16] {
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .expect("system random number generator failed");
+    salt
+}
+
+/// Parses a `$pbkdf2-sha256$<iterations>$<salt>$<hash>` password hash.
+fn parse_hash(hash: &str) -> Option<PasswordHash> {
+    let mut parts = hash.strip_prefix("$pbkdf2-sha256$")?.split('$');
+    let iterations = NonZeroU32::new(parts.next()?.parse().ok()?)?;
+    let salt = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+    let hash = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+    if parts.next().is_some() || hash.is_empty() {
+        return None;
+    }
+    Some(PasswordHash {
+        iterations,
+        salt,
+        hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::middleware::run_middleware;
+    use fluxio::body::to_bytes;
+    use fluxio::{Body, Request, Response};
+
+    const USERS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/core/testdata/users.txt");
+    const USERS_INVALID: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/core/testdata/users_invalid.txt"
+    );
+
+    /// Answers with the name and scheme of the authenticated user.
+    fn whoami(req: Req, _params: Params) -> Reply {
+        let body = match principal(&req) {
+            Some(principal) => format!("{} ({})", principal.name, principal.scheme),
+            None => "anonymous".to_string(),
+        };
+        boxed(async move { Ok(Response::new(Body::from(body))) })
+    }
+
+    fn request(authorization: Option<&str>) -> Req {
+        let mut builder = Request::get("/");
+        if let Some(value) = authorization {
+            builder = builder.header(AUTHORIZATION, value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn basic(user: &str, password: &str) -> String {
+        format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", user, password))
+        )
+    }
+
+    async fn body_text(response: fluxio::Response<Body>) -> String {
+        String::from_utf8(to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn users_file_skips_comments_and_blank_lines() {
+        let auth = BasicAuth::from_users_file("Admin", USERS).unwrap();
+
+        for (user, password) in [("admin", "s3cret"), ("alice", "hunter2")] {
+            let req = request(Some(&basic(user, password)));
+            let response = run_middleware(auth.clone(), req, whoami).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(body_text(response).await, format!("{} (Basic)", user));
+        }
+    }
+
+    #[test]
+    fn users_file_reports_the_invalid_line() {
+        let error = match BasicAuth::from_users_file("Admin", USERS_INVALID) {
+            Ok(_) => panic!("invalid users file accepted"),
+            Err(error) => error,
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid users file entry on line 4");
+    }
+
+    #[tokio::test]
+    async fn wrong_passwords_and_unknown_users_are_rejected() {
+        let auth = BasicAuth::from_users_file("Admin", USERS).unwrap();
+
+        for authorization in [
+            Some(basic("admin", "hunter2")),
+            Some(basic("mallory", "s3cret")),
+            Some("Basic not-base64!".to_string()),
+            None,
+        ] {
+            let response =
+                run_middleware(auth.clone(), request(authorization.as_deref()), whoami).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.headers()[WWW_AUTHENTICATE],
+                r#"Basic realm="Admin", charset="UTF-8""#
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_set_the_principal() {
+        let auth = BearerAuth::new("Metrics \"internal\"", |token| {
+            (token == "scraper-token").then(|| "prometheus".to_string())
+        });
+
+        let response =
+            run_middleware(auth.clone(), request(Some("Bearer scraper-token")), whoami).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "prometheus (Bearer)");
+
+        let response = run_middleware(auth.clone(), request(None), whoami).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Metrics \"internal\"""#
+        );
+
+        let response = run_middleware(auth, request(Some("Bearer stolen")), whoami).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Metrics \"internal\"", error="invalid_token""#
+        );
+    }
+}
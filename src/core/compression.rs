@@ -0,0 +1,270 @@
+use super::{Middleware, Next, Params, Reply, Req, boxed};
+use flate2::Compression as Level;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use fluxio::body::{HttpBody, to_bytes};
+use fluxio::header::{self, HeaderMap, HeaderValue};
+use fluxio::{Body, Method, Response, StatusCode};
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// A content coding supported by the `Compression` middleware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,  // `br`
+    Gzip,    // `gzip`
+    Deflate, // `deflate` (zlib format)
+}
+
+impl Encoding {
+    /// Returns the token of the coding in `Accept-Encoding` and `Content-Encoding`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Compresses data with this coding.
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                encoder.write_all(data)?;
+                Ok(encoder.into_inner())
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Response compression middleware, negotiated with `Accept-Encoding`.
+///
+/// Compresses responses whose body is at least `min_size` bytes and whose content type is
+/// in the allowlist, using the coding preferred by the client among `br`, `gzip` and
+/// `deflate` (ties go to that order). Responses that already carry `Content-Encoding` or
+/// `Cache-Control: no-transform`, responses to HEAD requests, and streamed bodies of
+/// unknown size are sent unchanged. Add it with `Fluxor::wrap` so static files are
+/// compressed too.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// let mut app = Fluxor::new();
+/// app.wrap(
+///     Compression::new()
+///         .min_size(1024)
+///         .content_types(&["application/json", "text/csv", "text/*"]),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Compression {
+    encodings: Vec<Encoding>,     // Enabled codings, in order of preference
+    min_size: u64,                // Smallest body size to compress, in bytes
+    content_types: Arc<[String]>, // Compressible content types (`type/*` matches a whole type)
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compression {
+    /// Creates a compression middleware with every coding enabled, a 1 KiB threshold and
+    /// an allowlist of common text formats (HTML, CSS, JavaScript, JSON, XML, CSV, SVG).
+    ///
+    /// # Returns
+    ///
+    /// A new Compression instance.
+    pub fn new() -> Self {
+        Self {
+            encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+            min_size: 1024,
+            content_types: [
+                "text/*",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "application/xhtml+xml",
+                "application/rss+xml",
+                "application/atom+xml",
+                "application/wasm",
+                "image/svg+xml",
+            ]
+            .iter()
+            .map(|content_type| content_type.to_string())
+            .collect(),
+        }
+    }
+
+    /// Sets the enabled codings, in order of preference for ties in `Accept-Encoding`.
+    ///
+    /// # Arguments
+    ///
+    /// * `encodings`: The enabled codings.
+    pub fn encodings(mut self, encodings: &[Encoding]) -> Self {
+        self.encodings = encodings.to_vec();
+        self
+    }
+
+    /// Sets the smallest body size to compress (1024 bytes by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The threshold in bytes.
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Sets the compressible content types, replacing the default allowlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_types`: Content types such as "application/json", or "text/*" for a whole type.
+    pub fn content_types(mut self, content_types: &[&str]) -> Self {
+        self.content_types = content_types
+            .iter()
+            .map(|content_type| content_type.to_ascii_lowercase())
+            .collect();
+        self
+    }
+
+    /// Picks the coding to use for an `Accept-Encoding` header, if any.
+    fn negotiate(&self, accept_encoding: &str) -> Option<Encoding> {
+        let mut best: Option<(Encoding, f32)> = None;
+        for encoding in &self.encodings {
+            let quality = quality_of(accept_encoding, encoding.as_str());
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((*encoding, quality));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply {
+        let encoding = match req.method() {
+            &Method::HEAD => None,
+            _ => req
+                .headers()
+                .get(header::ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| self.negotiate(value)),
+        };
+        let content_types = self.content_types.clone();
+        let min_size = self.min_size;
+
+        boxed(async move {
+            let mut response = next.run(req, params).await?;
+            let headers = response.headers();
+
+            if !is_compressible(&content_types, headers)
+                || headers.contains_key(header::CONTENT_ENCODING)
+                || matches!(
+                    response.status(),
+                    StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED
+                )
+            {
+                return Ok(response);
+            }
+            response
+                .headers_mut()
+                .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+
+            let no_transform = response
+                .headers()
+                .get_all(header::CACHE_CONTROL)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| value.to_ascii_lowercase().contains("no-transform"));
+            let size = response.body().size_hint().exact();
+            let (Some(encoding), Some(size)) = (encoding, size) else {
+                return Ok(response);
+            };
+            if no_transform || size < min_size {
+                return Ok(response);
+            }
+
+            let (mut parts, body) = response.into_parts();
+            let data = match to_bytes(body).await {
+                Ok(data) => data,
+                Err(_) => return Ok(Response::from_parts(parts, Body::empty())),
+            };
+
+            // Compress off the async runtime, keeping the original body if it does not shrink
+            let original = data.clone();
+            let compressed = tokio::task::spawn_blocking(move || encoding.encode(&data)).await;
+            match compressed {
+                Ok(Ok(compressed)) if compressed.len() < original.len() => {
+                    parts.headers.insert(
+                        header::CONTENT_ENCODING,
+                        HeaderValue::from_static(encoding.as_str()),
+                    );
+                    parts
+                        .headers
+                        .insert(header::CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+                    Ok(Response::from_parts(parts, Body::from(compressed)))
+                }
+                _ => Ok(Response::from_parts(parts, Body::from(original))),
+            }
+        })
+    }
+}
+
+/// Checks whether a response content type is in an allowlist.
+fn is_compressible(content_types: &[String], headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    content_types
+        .iter()
+        .any(|allowed| match allowed.strip_suffix("/*") {
+            Some(kind) => content_type
+                .split_once('/')
+                .is_some_and(|(main, _)| main == kind),
+            None => *allowed == content_type,
+        })
+}
+
+/// Returns the quality value of a coding in an `Accept-Encoding` header (0 if not accepted).
+fn quality_of(accept_encoding: &str, coding: &str) -> f32 {
+    let mut wildcard = None;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|value| value.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(coding) {
+            return quality;
+        }
+        if name == "*" {
+            wildcard = Some(quality);
+        }
+    }
+    wildcard.unwrap_or(0.0)
+}
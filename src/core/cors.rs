@@ -0,0 +1,388 @@
+use super::{Middleware, Next, Params, Reply, Req, boxed};
+use fluxio::header::{self, HeaderMap, HeaderValue};
+use fluxio::{Body, Method, Response, StatusCode};
+use std::sync::Arc;
+
+/// The origins allowed by a `Cors` middleware.
+#[derive(Clone)]
+pub enum AllowOrigin {
+    Any,                                                // Any origin
+    List(Vec<String>),                                  // Exact origins, e.g. "https://example.com"
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>), // Origins accepted by a closure
+}
+
+impl AllowOrigin {
+    /// Checks whether an origin is allowed.
+    fn allows(&self, origin: &str) -> bool {
+        match self {
+            AllowOrigin::Any => true,
+            AllowOrigin::List(origins) => origins.iter().any(|allowed| allowed == origin),
+            AllowOrigin::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+/// Cross-Origin Resource Sharing middleware.
+///
+/// Adds `Access-Control-*` headers to responses for requests from allowed origins and
+/// answers preflight requests (`OPTIONS` with `Access-Control-Request-Method`) with
+/// `204 No Content`. Requests without an `Origin` header, or from origins that are not
+/// allowed, pass through unchanged.
+///
+/// Preflight requests are not dispatched to route handlers, so add it with `Fluxor::wrap`
+/// (globally or on a mounted group) rather than `Route::wrap`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// let mut app = Fluxor::new();
+/// app.wrap(
+///     Cors::new()
+///         .allow_origins(&["https://app.example.com", "https://admin.example.com"])
+///         .allow_methods(&[GET, POST, DELETE])
+///         .allow_headers(&["Content-Type", "Authorization"])
+///         .allow_credentials(true)
+///         .max_age(3600),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Cors {
+    origins: AllowOrigin,         // Allowed origins
+    methods: Vec<Method>,         // Methods allowed in preflight answers
+    headers: Option<Vec<String>>, // Request headers allowed in preflight answers (`None` mirrors the request)
+    expose_headers: Vec<String>,  // Response headers exposed to the browser
+    credentials: bool,            // Allow cookies and authorization headers
+    max_age: Option<u64>,         // How long browsers may cache preflight answers, in seconds
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cors {
+    /// Creates a CORS middleware allowing any origin, the common methods and any request header.
+    ///
+    /// # Returns
+    ///
+    /// A new Cors instance.
+    pub fn new() -> Self {
+        Self {
+            origins: AllowOrigin::Any,
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ],
+            headers: None,
+            expose_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allows an exact origin, in addition to the ones already allowed with this method.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin`: The origin (e.g. "https://example.com").
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        match &mut self.origins {
+            AllowOrigin::List(origins) => origins.push(origin.to_string()),
+            origins => *origins = AllowOrigin::List(vec![origin.to_string()]),
+        }
+        self
+    }
+
+    /// Allows a list of exact origins, replacing the allowed origins.
+    ///
+    /// # Arguments
+    ///
+    /// * `origins`: The origins (e.g. `&["https://example.com"]`).
+    pub fn allow_origins(mut self, origins: &[&str]) -> Self {
+        self.origins = AllowOrigin::List(origins.iter().map(|origin| origin.to_string()).collect());
+        self
+    }
+
+    /// Allows the origins accepted by a predicate, replacing the allowed origins.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: A closure receiving the `Origin` header value.
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins = AllowOrigin::Predicate(Arc::new(predicate));
+        self
+    }
+
+    /// Allows any origin (the default).
+    ///
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed (see `allow_credentials`).
+    pub fn allow_any_origin(mut self) -> Self {
+        assert!(
+            !self.credentials,
+            "Cors: credentials cannot be allowed for any origin"
+        );
+        self.origins = AllowOrigin::Any;
+        self
+    }
+
+    /// Sets the methods allowed in preflight answers.
+    ///
+    /// # Arguments
+    ///
+    /// * `methods`: The allowed methods.
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    /// Sets the request headers allowed in preflight answers.
+    ///
+    /// By default, the headers listed in `Access-Control-Request-Headers` are allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers`: The allowed header names.
+    pub fn allow_headers(mut self, headers: &[&str]) -> Self {
+        self.headers = Some(headers.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Sets the response headers exposed to the browser (`Access-Control-Expose-Headers`).
+    ///
+    /// # Arguments
+    ///
+    /// * `headers`: The exposed header names.
+    pub fn expose_headers(mut self, headers: &[&str]) -> Self {
+        self.expose_headers = headers.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Allows or disallows credentials (`Access-Control-Allow-Credentials`).
+    ///
+    /// Credentialed requests can only be allowed for specific origins, so call
+    /// `allow_origin`, `allow_origins` or `allow_origin_fn` first: echoing any origin with
+    /// credentials would let every site make authenticated requests on behalf of the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled`: Whether credentials are allowed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `enabled` is true while any origin is allowed (the default).
+    pub fn allow_credentials(mut self, enabled: bool) -> Self {
+        assert!(
+            !enabled || !matches!(self.origins, AllowOrigin::Any),
+            "Cors: credentials cannot be allowed for any origin; call allow_origins or allow_origin_fn first"
+        );
+        self.credentials = enabled;
+        self
+    }
+
+    /// Sets how long browsers may cache preflight answers (`Access-Control-Max-Age`).
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds`: The duration in seconds.
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Returns the `Access-Control-Allow-Origin` value for an allowed origin, and whether
+    /// the response varies by origin.
+    fn allow_origin_value(&self, origin: &HeaderValue) -> (HeaderValue, bool) {
+        if self.varies_by_origin() {
+            (origin.clone(), true)
+        } else {
+            (HeaderValue::from_static("*"), false)
+        }
+    }
+
+    /// Whether responses depend on the `Origin` header, i.e. the allowed origin is echoed
+    /// rather than `*`, so caches must key them by origin.
+    fn varies_by_origin(&self) -> bool {
+        !matches!(self.origins, AllowOrigin::Any)
+    }
+
+    /// Builds the answer to a preflight request.
+    fn preflight_response(&self, req: &Req, origin: &HeaderValue) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+
+        add_origin_headers(headers, self.allow_origin_value(origin), self.credentials);
+
+        let methods: Vec<&str> = self.methods.iter().map(|method| method.as_str()).collect();
+        if let Ok(value) = HeaderValue::from_str(&methods.join(", ")) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+
+        match &self.headers {
+            Some(allowed) => {
+                if !allowed.is_empty()
+                    && let Ok(value) = HeaderValue::from_str(&allowed.join(", "))
+                {
+                    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+                }
+            }
+            None => {
+                if let Some(requested) = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+                    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+                    headers.append(
+                        header::VARY,
+                        HeaderValue::from_static("Access-Control-Request-Headers"),
+                    );
+                }
+            }
+        }
+
+        if let Some(seconds) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+impl Middleware for Cors {
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply {
+        let origin = match req.headers().get(header::ORIGIN) {
+            Some(origin)
+                if origin
+                    .to_str()
+                    .is_ok_and(|value| self.origins.allows(value)) =>
+            {
+                origin.clone()
+            }
+            // Not a CORS request, or a disallowed origin: the response still varies by origin
+            _ if self.varies_by_origin() => {
+                return boxed(async move {
+                    let mut response = next.run(req, params).await?;
+                    response
+                        .headers_mut()
+                        .append(header::VARY, HeaderValue::from_static("Origin"));
+                    Ok(response)
+                });
+            }
+            _ => return next.run(req, params),
+        };
+
+        if req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let response = self.preflight_response(&req, &origin);
+            return boxed(async move { Ok(response) });
+        }
+
+        let allow_origin = self.allow_origin_value(&origin);
+        let credentials = self.credentials;
+        let expose = match self.expose_headers.is_empty() {
+            true => None,
+            false => HeaderValue::from_str(&self.expose_headers.join(", ")).ok(),
+        };
+
+        boxed(async move {
+            let mut response = next.run(req, params).await?;
+            let headers = response.headers_mut();
+            add_origin_headers(headers, allow_origin, credentials);
+            if let Some(value) = expose {
+                headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Adds the headers shared by preflight and actual responses.
+fn add_origin_headers(
+    headers: &mut HeaderMap,
+    (allow_origin, vary): (HeaderValue, bool),
+    credentials: bool,
+) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+    if vary {
+        headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+    if credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::middleware::run_middleware;
+    use fluxio::Request;
+
+    fn ok(_req: Req, _params: Params) -> Reply {
+        boxed(async { Ok(Response::new(Body::empty())) })
+    }
+
+    #[test]
+    #[should_panic(expected = "credentials cannot be allowed for any origin")]
+    fn credentials_are_rejected_for_any_origin() {
+        let _ = Cors::new().allow_credentials(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "credentials cannot be allowed for any origin")]
+    fn any_origin_is_rejected_with_credentials() {
+        let _ = Cors::new()
+            .allow_origin("https://app.example.com")
+            .allow_credentials(true)
+            .allow_any_origin();
+    }
+
+    #[tokio::test]
+    async fn credentials_echo_allowed_origins_only() {
+        let cors = Cors::new()
+            .allow_origins(&["https://app.example.com"])
+            .allow_credentials(true);
+
+        let req = Request::get("/")
+            .header(header::ORIGIN, "https://app.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = run_middleware(cors.clone(), req, ok).await;
+        let headers = response.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::VARY], "Origin");
+
+        let req = Request::get("/")
+            .header(header::ORIGIN, "https://evil.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = run_middleware(cors, req, ok).await;
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+        );
+    }
+}
@@ -0,0 +1,723 @@
+use super::{Middleware, Next, Params, Reply, Req, boxed, error_response};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use fluxio::body::{Bytes, HttpBody};
+use fluxio::header::{self, HeaderValue};
+use fluxio::{Body, Method, StatusCode};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::Arc;
+
+/// The CSRF token of a request, stored in the request extensions by `Csrf`
+/// (see `csrf_token` and `csrf_field`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsrfToken {
+    pub token: String, // Token to submit with forms and unsafe requests
+    pub field: String, // Name of the form field carrying the token
+}
+
+/// Returns the CSRF token of a request.
+///
+/// # Arguments
+///
+/// * `req`: The request.
+///
+/// # Returns
+///
+/// The token to send back in the CSRF header or form field, or `None` if the request
+/// did not go through a `Csrf` middleware.
+pub fn csrf_token(req: &Req) -> Option<&str> {
+    req.extensions()
+        .get::<CsrfToken>()
+        .map(|token| token.token.as_str())
+}
+
+/// Returns a hidden form field carrying the CSRF token of a request, to insert into forms.
+///
+/// # Arguments
+///
+/// * `req`: The request.
+///
+/// # Returns
+///
+/// An `<input type="hidden">` element, or an empty string if the request did not go
+/// through a `Csrf` middleware.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// const FORM: &str = r#"<form method="post" action="/profile">
+///     {{csrf_field}}
+///     <input name="email">
+///     <button>Save</button>
+/// </form>"#;
+///
+/// fn edit_profile(req: Req, _params: Params) -> Reply {
+///     let html = do_html!(FORM, csrf_field = csrf_field(&req));
+///     boxed(async move {
+///         Ok(Response::builder()
+///             .header("Content-Type", "text/html; charset=UTF-8")
+///             .body(Body::from(html))
+///             .unwrap())
+///     })
+/// }
+/// ```
+pub fn csrf_field(req: &Req) -> String {
+    req.extensions()
+        .get::<CsrfToken>()
+        .map_or(String::new(), |token| {
+            format!(
+                r#"<input type="hidden" name="{}" value="{}">"#,
+                token.field, token.token
+            )
+        })
+}
+
+/// CSRF protection middleware, using signed double-submit cookies.
+///
+/// Every request gets a token, kept in a cookie signed with the secret and available to
+/// handlers with `csrf_token` and `csrf_field`. POST, PUT, PATCH and DELETE requests must
+/// send the token of their cookie back in the CSRF header or in a form field
+/// (`application/x-www-form-urlencoded` or `multipart/form-data`), otherwise they get
+/// `403 Forbidden`. JSON requests can be exempted with `exempt_json`, since browsers
+/// cannot send them cross-origin without a CORS preflight.
+///
+/// Multipart bodies are only read up to the part carrying the token and the rest streams
+/// through to the handler, so uploads are limited by `Route::body_limit` alone. Put the
+/// token field before the file fields of upload forms (see `max_form_size`).
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn save_profile(_req: Req, _params: Params) -> Reply {
+///     boxed(async { Ok(Response::new(Body::from("Saved"))) })
+/// }
+///
+/// let mut app = Fluxor::new();
+/// app.wrap(Csrf::new(b"a long random secret").secure(true).exempt_json(true));
+/// app.route(POST, "/profile", save_profile);
+/// ```
+#[derive(Clone)]
+pub struct Csrf {
+    key: Arc<hmac::Key>, // Signs the tokens
+    cookie_name: String, // Name of the token cookie
+    header_name: String, // Name of the header carrying the token
+    field_name: String,  // Name of the form field carrying the token
+    secure: bool,        // Whether the cookie is only sent over HTTPS
+    exempt_json: bool,   // Whether JSON requests skip validation
+    max_form_size: u64,  // Largest form body searched for the token, in bytes
+}
+
+impl Csrf {
+    /// Creates a CSRF middleware with the `csrf_token` cookie and form field and the
+    /// `X-CSRF-Token` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret`: The secret signing the tokens, at least 32 random bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new Csrf instance.
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: Arc::new(hmac::Key::new(hmac::HMAC_SHA256, secret)),
+            cookie_name: "csrf_token".to_string(),
+            header_name: "X-CSRF-Token".to_string(),
+            field_name: "csrf_token".to_string(),
+            secure: false,
+            exempt_json: false,
+            max_form_size: 1024 * 1024,
+        }
+    }
+
+    /// Sets the name of the token cookie.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The cookie name (e.g. "__Host-csrf" with `secure(true)`).
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// Sets the name of the header carrying the token.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The header name.
+    pub fn header_name(mut self, name: &str) -> Self {
+        self.header_name = name.to_string();
+        self
+    }
+
+    /// Sets the name of the form field carrying the token.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The field name.
+    pub fn field_name(mut self, name: &str) -> Self {
+        self.field_name = name.to_string();
+        self
+    }
+
+    /// Sets whether the cookie has the `Secure` attribute (false by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `secure`: Whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets whether requests with an `application/json` body skip validation (false by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `exempt`: Whether JSON requests are exempt.
+    pub fn exempt_json(mut self, exempt: bool) -> Self {
+        self.exempt_json = exempt;
+        self
+    }
+
+    /// Sets the largest form body searched for the token (1 MiB by default).
+    ///
+    /// Urlencoded forms are read whole, and larger ones get `413 Payload Too Large` unless
+    /// they send the token in the header. Multipart forms are read until the part carrying
+    /// the token; they only get a 413 when more than this many bytes precede it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: The limit in bytes.
+    pub fn max_form_size(mut self, bytes: u64) -> Self {
+        self.max_form_size = bytes;
+        self
+    }
+
+    /// Generates a new signed token.
+    fn generate(&self) -> String {
+        let mut nonce = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("system random number generator failed");
+        let nonce = URL_SAFE_NO_PAD.encode(nonce);
+        let signature = hmac::sign(&self.key, nonce.as_bytes());
+        format!("{}.{}", nonce, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Checks the signature of a token.
+    fn is_signed(&self, token: &str) -> bool {
+        token.split_once('.').is_some_and(|(nonce, signature)| {
+            URL_SAFE_NO_PAD.decode(signature).is_ok_and(|signature| {
+                hmac::verify(&self.key, nonce.as_bytes(), &signature).is_ok()
+            })
+        })
+    }
+
+    /// Returns the signed token of the request cookie, if any.
+    fn cookie_token(&self, req: &Req) -> Option<String> {
+        req.headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == self.cookie_name)
+            .map(|(_, token)| token.to_string())
+            .filter(|token| self.is_signed(token))
+    }
+
+    /// Builds the `Set-Cookie` header storing a token.
+    fn set_cookie(&self, token: &str) -> Option<HeaderValue> {
+        let secure = if self.secure { "; Secure" } else { "" };
+        HeaderValue::from_str(&format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax{}",
+            self.cookie_name, token, secure
+        ))
+        .ok()
+    }
+}
+
+impl Middleware for Csrf {
+    fn handle(&self, mut req: Req, params: Params, next: Next) -> Reply {
+        let existing = self.cookie_token(&req);
+        let token = existing.clone().unwrap_or_else(|| self.generate());
+        let set_cookie = match existing {
+            Some(_) => None,
+            None => self.set_cookie(&token),
+        };
+        req.extensions_mut().insert(CsrfToken {
+            token: token.clone(),
+            field: self.field_name.clone(),
+        });
+
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string(); // Kept as is: the multipart boundary is case-sensitive
+        let unsafe_method = matches!(
+            *req.method(),
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        );
+        let exempt = self.exempt_json && media_type(&content_type) == "application/json";
+        let header_token = req
+            .headers()
+            .get(self.header_name.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let field = self.field_name.clone();
+        let max_form_size = self.max_form_size;
+
+        boxed(async move {
+            let mut response = 'response: {
+                if unsafe_method && !exempt {
+                    let submitted = match header_token {
+                        Some(submitted) => Some(submitted),
+                        None if is_form(&content_type) => {
+                            let (parts, body) = req.into_parts();
+                            let read = read_form_token(body, &content_type, &field, max_form_size);
+                            let Some((submitted, body)) = read.await else {
+                                let req = Req::from_parts(parts, Body::empty());
+                                break 'response error_response(
+                                    &req,
+                                    StatusCode::PAYLOAD_TOO_LARGE,
+                                    None,
+                                );
+                            };
+                            req = Req::from_parts(parts, body);
+                            submitted
+                        }
+                        None => None,
+                    };
+                    // The cookie must predate the request: a freshly generated token proves nothing
+                    let valid = existing.is_some()
+                        && submitted.is_some_and(|submitted| {
+                            equals(submitted.as_bytes(), token.as_bytes())
+                        });
+                    if !valid {
+                        break 'response error_response(&req, StatusCode::FORBIDDEN, None);
+                    }
+                }
+                next.run(req, params).await?
+            };
+
+            if let Some(set_cookie) = set_cookie {
+                response
+                    .headers_mut()
+                    .append(header::SET_COOKIE, set_cookie);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Returns the media type of a `Content-Type` value, lowercased and without parameters.
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Checks whether a content type is a form that may carry the token.
+fn is_form(content_type: &str) -> bool {
+    matches!(
+        media_type(content_type).as_str(),
+        "application/x-www-form-urlencoded" | "multipart/form-data"
+    )
+}
+
+/// Reads a body, or returns `None` if it is larger than `limit` bytes or fails.
+async fn read_body(mut body: Body, limit: u64) -> Option<Bytes> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.ok()?;
+        if (data.len() + chunk.len()) as u64 > limit {
+            return None;
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Some(Bytes::from(data))
+}
+
+/// Reads the token field of a form body, returning it with the body to forward.
+///
+/// Urlencoded bodies are read whole. Multipart bodies are read until the part carrying
+/// the token is complete; the rest of the body is forwarded unchanged as it arrives.
+///
+/// # Arguments
+///
+/// * `body`: The request body.
+/// * `content_type`: The `Content-Type` of the request.
+/// * `field`: The name of the token field.
+/// * `limit`: The largest number of bytes read while looking for the token.
+///
+/// # Returns
+///
+/// The submitted token, if any, and the body for the handler, or `None` if the limit is
+/// exceeded before the token is found or the body fails.
+async fn read_form_token(
+    mut body: Body,
+    content_type: &str,
+    field: &str,
+    limit: u64,
+) -> Option<(Option<String>, Body)> {
+    if media_type(content_type) != "multipart/form-data" {
+        let data = read_body(body, limit).await?;
+        let submitted = url::form_urlencoded::parse(&data)
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.into_owned());
+        return Some((submitted, Body::from(data)));
+    }
+
+    let Some(boundary) = content_type
+        .split(';')
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, boundary)| boundary.trim().trim_matches('"'))
+    else {
+        return Some((None, body)); // Not a valid multipart body, it cannot carry the token
+    };
+    let delimiter = format!("--{}", boundary);
+
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.ok()?;
+        // A part is only complete once the next delimiter has arrived
+        let start = data.len().saturating_sub(delimiter.len());
+        data.extend_from_slice(&chunk);
+        if data[start..]
+            .windows(delimiter.len())
+            .any(|window| window == delimiter.as_bytes())
+            && let Some(submitted) = multipart_value(&data, &delimiter, field)
+        {
+            return Some((Some(submitted), forward(Bytes::from(data), body)));
+        }
+        if data.len() as u64 > limit {
+            return None;
+        }
+    }
+    let submitted = multipart_value(&data, &delimiter, field);
+    Some((submitted, Body::from(data)))
+}
+
+/// Returns a body made of the bytes already read followed by the rest of `body`.
+fn forward(prefix: Bytes, mut body: Body) -> Body {
+    let (mut sender, forwarded) = Body::channel();
+    tokio::spawn(async move {
+        if sender.send_data(prefix).await.is_err() {
+            return; // The handler dropped the body
+        }
+        while let Some(chunk) = body.data().await {
+            let Ok(chunk) = chunk else {
+                return sender.abort();
+            };
+            if sender.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+        if let Ok(Some(trailers)) = body.trailers().await {
+            let _ = sender.send_trailers(trailers).await;
+        }
+    });
+    forwarded
+}
+
+/// Returns the value of a field among the complete parts of a (possibly partial)
+/// multipart body.
+fn multipart_value(data: &[u8], delimiter: &str, field: &str) -> Option<String> {
+    let body = String::from_utf8_lossy(data);
+    let parts: Vec<&str> = body.split(delimiter).collect();
+    // Skip the preamble, and the last part, which is not followed by a delimiter yet
+    parts.get(1..parts.len() - 1)?.iter().find_map(|part| {
+        let (headers, value) = part.split_once("\r\n\r\n")?;
+        headers
+            .lines()
+            .any(|line| disposition_name(line) == Some(field))
+            .then(|| value.strip_suffix("\r\n").unwrap_or(value).to_string())
+    })
+}
+
+/// Returns the `name` parameter of a `Content-Disposition` header line, if it is one.
+fn disposition_name(line: &str) -> Option<&str> {
+    let (header, value) = line.split_once(':')?;
+    if !header.trim().eq_ignore_ascii_case("content-disposition") {
+        return None;
+    }
+    value
+        .split(';')
+        .skip(1) // The disposition type, e.g. `form-data`
+        .filter_map(|param| param.trim().split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("name"))
+        .map(|(_, value)| value.trim().trim_matches('"'))
+}
+
+/// Compares two byte strings in constant time for a given length.
+fn equals(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::middleware::run_middleware;
+    use fluxio::body::to_bytes;
+    use fluxio::{Request, Response};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    /// Answers with the request body, to check it reaches the handler intact.
+    fn echo(req: Req, _params: Params) -> Reply {
+        boxed(async move {
+            let body = to_bytes(req.into_body()).await.unwrap();
+            Ok(Response::new(Body::from(body)))
+        })
+    }
+
+    /// Sends a request through a `Csrf` middleware. `{token}` in the cookie, header values
+    /// and body is replaced with a validly signed token.
+    async fn send(
+        csrf: Csrf,
+        method: Method,
+        cookie: Option<&str>,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> Response<Body> {
+        let token = csrf.generate();
+        let mut builder = Request::builder().method(method).uri("/profile");
+        if let Some(cookie) = cookie {
+            let cookie = format!(
+                "theme=dark; csrf_token={}",
+                cookie.replace("{token}", &token)
+            );
+            builder = builder.header(header::COOKIE, cookie);
+        }
+        for (name, value) in headers {
+            builder = builder.header(*name, value.replace("{token}", &token));
+        }
+        let req = builder
+            .body(Body::from(body.replace("{token}", &token)))
+            .unwrap();
+        run_middleware(csrf, req, echo).await
+    }
+
+    const FORM: (&str, &str) = ("Content-Type", "application/x-www-form-urlencoded");
+
+    #[tokio::test]
+    async fn safe_methods_get_a_token_cookie() {
+        let response = send(Csrf::new(SECRET), Method::GET, None, &[], "").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("csrf_token="));
+        assert!(cookie.ends_with("; Path=/; HttpOnly; SameSite=Lax"));
+
+        // A valid cookie is kept as is
+        let response = send(Csrf::new(SECRET), Method::GET, Some("{token}"), &[], "").await;
+        assert!(!response.headers().contains_key(header::SET_COOKIE));
+    }
+
+    #[tokio::test]
+    async fn accepts_header_token() {
+        let headers = [("X-CSRF-Token", "{token}")];
+        let response = send(
+            Csrf::new(SECRET),
+            Method::DELETE,
+            Some("{token}"),
+            &headers,
+            "",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn accepts_urlencoded_field() {
+        let body = "email=a%40b.c&csrf_token={token}";
+        let response = send(
+            Csrf::new(SECRET),
+            Method::POST,
+            Some("{token}"),
+            &[FORM],
+            body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = to_bytes(response.into_body()).await.unwrap();
+        assert!(echoed.starts_with(b"email=a%40b.c&csrf_token="));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_wrong_tokens() {
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            let response = send(Csrf::new(SECRET), method, Some("{token}"), &[FORM], "a=1").await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        let body = "csrf_token=wrong";
+        let response = send(
+            Csrf::new(SECRET),
+            Method::POST,
+            Some("{token}"),
+            &[FORM],
+            body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_forged_cookies() {
+        // Without a cookie, the token generated for this request proves nothing
+        let headers = [("X-CSRF-Token", "{token}")];
+        let response = send(Csrf::new(SECRET), Method::POST, None, &headers, "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+
+        // A cookie not signed with the secret is replaced, not trusted
+        let headers = [("X-CSRF-Token", "forged.token")];
+        let response = send(
+            Csrf::new(SECRET),
+            Method::POST,
+            Some("forged.token"),
+            &headers,
+            "",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let other = Csrf::new(b"another secret").generate();
+        let headers = [("X-CSRF-Token", other.as_str())];
+        let response = send(Csrf::new(SECRET), Method::POST, Some(&other), &headers, "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn exempts_json_only_when_configured() {
+        let json = [("Content-Type", "Application/JSON; charset=utf-8")];
+        let response = send(Csrf::new(SECRET), Method::POST, None, &json, "{}").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let csrf = Csrf::new(SECRET).exempt_json(true);
+        let response = send(csrf.clone(), Method::POST, None, &json, "{}").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(csrf, Method::POST, None, &[FORM], "a=1").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rejects_forms_over_max_size() {
+        let csrf = Csrf::new(SECRET).max_form_size(64);
+        let body = format!("csrf_token={{token}}&padding={}", "x".repeat(64));
+        let response = send(csrf.clone(), Method::POST, Some("{token}"), &[FORM], &body).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // The header still works for large bodies, which are not read
+        let headers = [FORM, ("X-CSRF-Token", "{token}")];
+        let response = send(csrf, Method::POST, Some("{token}"), &headers, &body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn accepts_multipart_field_with_mixed_case_boundary() {
+        let boundary = "----WebKitFormBoundary7MA4YWxkTrZu0gW";
+        let body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{{token}}\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"email\"\r\n\r\na@b.c\r\n--{b}--\r\n",
+            b = boundary
+        );
+        let content_type = format!("multipart/form-data; boundary={}", boundary);
+        let headers = [("Content-Type", content_type.as_str())];
+        let response = send(
+            Csrf::new(SECRET),
+            Method::POST,
+            Some("{token}"),
+            &headers,
+            &body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = to_bytes(response.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&echoed).contains("a@b.c"));
+    }
+
+    /// Builds a multipart body with the token part at the given position among a text
+    /// field and a file of `file_size` bytes.
+    fn upload_body(boundary: &str, token_first: bool, file_size: usize) -> String {
+        let token = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{{token}}\r\n",
+            boundary
+        );
+        let file = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n{}\r\n",
+            boundary,
+            "x".repeat(file_size)
+        );
+        let (first, second) = if token_first {
+            (token, file)
+        } else {
+            (file, token)
+        };
+        format!("{}{}--{}--\r\n", first, second, boundary)
+    }
+
+    /// Sends a multipart upload through a `Csrf` middleware, streamed in small chunks.
+    async fn upload(csrf: Csrf, body: &str) -> (Response<Body>, String) {
+        let token = csrf.generate();
+        let body = body.replace("{token}", &token);
+        let (mut sender, stream) = Body::channel();
+        let chunks: Vec<Bytes> = body
+            .as_bytes()
+            .chunks(100)
+            .map(Bytes::copy_from_slice)
+            .collect();
+        tokio::spawn(async move {
+            for chunk in chunks {
+                if sender.send_data(chunk).await.is_err() {
+                    return;
+                }
+            }
+        });
+        let req = Request::post("/upload")
+            .header(header::COOKIE, format!("csrf_token={}", token))
+            .header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .body(stream)
+            .unwrap();
+        (run_middleware(csrf, req, echo).await, body)
+    }
+
+    #[tokio::test]
+    async fn streams_uploads_after_the_token_part() {
+        let csrf = Csrf::new(SECRET).max_form_size(256);
+
+        let (response, sent) = upload(csrf.clone(), &upload_body("XyZ", true, 4096)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(echoed, sent.as_bytes());
+
+        // The token must come before the limit when it follows the file
+        let (response, _) = upload(csrf, &upload_body("XyZ", false, 4096)).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn ignores_file_names_matching_the_field() {
+        let body = "--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"csrf_token\"\r\n\r\n\
+                    {token}\r\n--XyZ--\r\n";
+        let (response, _) = upload(Csrf::new(SECRET), body).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = "--XyZ\r\ncontent-disposition: form-data; filename=\"a.txt\"; NAME=csrf_token\r\n\r\n\
+                    {token}\r\n--XyZ--\r\n";
+        let (response, _) = upload(Csrf::new(SECRET), body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,111 @@
+use super::Route;
+use fluxio::Method;
+use styledlog::Colorize;
+
+/// A description of a registered route (see `Fluxor::route_table`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteInfo {
+    pub method: Method,          // HTTP method of the route
+    pub path: String,            // Path pattern of the route
+    pub name: Option<String>,    // Name of the route, if any
+    pub host: Option<String>,    // Host pattern of the route, if any
+    pub middleware: Vec<String>, // Names of the route's group and route middleware, outermost first
+}
+
+impl RouteInfo {
+    /// Describes a registered route.
+    pub(crate) fn from_route(route: &Route) -> Self {
+        Self {
+            method: route.method.clone(),
+            path: route.path.clone(),
+            name: route.name.clone(),
+            host: route.host.clone(),
+            middleware: route
+                .middleware
+                .iter()
+                .map(|middleware| middleware.name())
+                .collect(),
+        }
+    }
+}
+
+/// Serializes a route table as a JSON array of objects.
+///
+/// # Arguments
+///
+/// * `table`: The route table to serialize.
+///
+/// # Returns
+///
+/// A JSON string such as
+/// `[{"method": "GET", "path": "/users/<id>", "name": null, "host": null, "middleware": []}]`.
+pub(crate) fn route_table_json(table: &[RouteInfo]) -> String {
+    let entries: Vec<String> = table
+        .iter()
+        .map(|info| {
+            let middleware: Vec<String> = info
+                .middleware
+                .iter()
+                .map(|name| json_string(name))
+                .collect();
+            format!(
+                r#"{{"method": {}, "path": {}, "name": {}, "host": {}, "middleware": [{}]}}"#,
+                json_string(info.method.as_str()),
+                json_string(&info.path),
+                info.name.as_deref().map_or("null".to_string(), json_string),
+                info.host.as_deref().map_or("null".to_string(), json_string),
+                middleware.join(", "),
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+/// Formats a route table for the startup banner, one route per line.
+pub(crate) fn route_table_text(table: &[RouteInfo]) -> String {
+    let width = table.iter().map(|info| info.path.len()).max().unwrap_or(0);
+    let host_width = table
+        .iter()
+        .map(|info| info.host.as_deref().map_or(0, |host| host.len() + 1))
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<String> = table
+        .iter()
+        .map(|info| {
+            let middleware = match info.middleware.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", info.middleware.join(", ")),
+            };
+            format!(
+                "{} {:<host_width$}{:<width$}  {}{}",
+                format!("{:<7}", info.method.as_str()).magenta(),
+                info.host.as_deref().unwrap_or(""),
+                info.path,
+                info.name.as_deref().unwrap_or("").italic(),
+                middleware.dimmed(),
+                host_width = host_width,
+                width = width,
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Encodes a string as a quoted JSON string, escaping quotes, backslashes and control characters.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() + 2);
+    encoded.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if (c as u32) < 0x20 => encoded.push_str(&format!("\\u{:04x}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
@@ -0,0 +1,968 @@
+use super::auth::{quote, scheme_value, unauthorized};
+use super::{Middleware, Next, Params, Principal, Reply, Req};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use ring::hmac;
+use ring::rand::SystemRandom;
+use ring::signature::{self, Ed25519KeyPair, KeyPair, RsaKeyPair, UnparsedPublicKey};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+use wtime::utc::utc_ts_sec;
+
+/// A JWT signature algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    HS256, // HMAC with SHA-256, shared secret
+    RS256, // RSASSA-PKCS1-v1_5 with SHA-256
+    EdDSA, // Ed25519
+}
+
+impl Algorithm {
+    /// Returns the name of the algorithm in the `alg` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::HS256 => "HS256",
+            Algorithm::RS256 => "RS256",
+            Algorithm::EdDSA => "EdDSA",
+        }
+    }
+}
+
+/// Why a token could not be issued or verified.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JwtError {
+    /// The token is not three base64url parts with a JSON header and payload.
+    Malformed,
+    /// The `alg` header does not match the algorithm of the key.
+    AlgorithmMismatch,
+    /// The signature does not match the header and payload.
+    InvalidSignature,
+    /// The `exp` claim is in the past, or missing when required.
+    Expired,
+    /// The `nbf` claim is in the future.
+    NotYetValid,
+    /// The `iss` claim does not match the expected issuer.
+    InvalidIssuer,
+    /// The `aud` claim does not contain the expected audience.
+    InvalidAudience,
+    /// The claims could not be serialized or deserialized into the requested type.
+    InvalidClaims(String),
+    /// The key cannot be parsed, or only verifies tokens and cannot sign them.
+    InvalidKey,
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtError::Malformed => write!(f, "malformed token"),
+            JwtError::AlgorithmMismatch => write!(f, "unexpected signature algorithm"),
+            JwtError::InvalidSignature => write!(f, "invalid signature"),
+            JwtError::Expired => write!(f, "token expired"),
+            JwtError::NotYetValid => write!(f, "token not yet valid"),
+            JwtError::InvalidIssuer => write!(f, "invalid issuer"),
+            JwtError::InvalidAudience => write!(f, "invalid audience"),
+            JwtError::InvalidClaims(message) => write!(f, "invalid claims: {}", message),
+            JwtError::InvalidKey => write!(f, "invalid key"),
+        }
+    }
+}
+
+impl Error for JwtError {}
+
+/// The signing or verification key of a `Jwt`.
+enum Key {
+    Hmac(hmac::Key),
+    Rsa {
+        pair: Option<RsaKeyPair>, // Private key, when the key can sign
+        public: Vec<u8>,          // DER-encoded RSAPublicKey
+    },
+    Ed25519 {
+        pair: Option<Ed25519KeyPair>, // Private key, when the key can sign
+        public: Vec<u8>,              // Raw 32-byte public key
+    },
+}
+
+/// The header of a token.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+/// The registered claims checked by `Jwt::verify`.
+#[derive(Deserialize)]
+struct Registered {
+    exp: Option<f64>,      // Expiration time, in seconds since the UNIX epoch
+    nbf: Option<f64>,      // Not before, in seconds since the UNIX epoch
+    iss: Option<String>,   // Issuer
+    aud: Option<Audience>, // Audience, one or several
+    sub: Option<String>,   // Subject, used as the principal name
+}
+
+/// The `aud` claim, a single string or an array.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(value) => value == audience,
+            Audience::Many(values) => values.iter().any(|value| value == audience),
+        }
+    }
+}
+
+/// Issues and verifies JSON Web Tokens signed with HS256, RS256 or EdDSA (Ed25519).
+///
+/// Verification checks the signature, the `alg` header against the key, `exp` (required by
+/// default) and `nbf` with a clock skew leeway, and `iss` and `aud` when configured. Claims
+/// are any type implementing serde's `Serialize` and `Deserialize`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+/// use fluxor::wtime::utc::utc_ts_sec;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Claims {
+///     sub: String,
+///     exp: u64,
+///     admin: bool,
+/// }
+///
+/// let jwt = Jwt::hs256(b"a long random secret").issuer("https://auth.example.com");
+/// let claims = Claims { sub: "alice".to_string(), exp: utc_ts_sec() + 3600, admin: true };
+///
+/// let token = jwt.sign(&claims).unwrap(); // Adds `iss` to the payload
+/// let verified: Claims = jwt.verify(&token).unwrap();
+/// assert_eq!(verified.sub, "alice");
+/// assert!(Jwt::hs256(b"another secret").verify::<Claims>(&token).is_err());
+/// ```
+#[derive(Clone)]
+pub struct Jwt {
+    algorithm: Algorithm,     // Algorithm of the key
+    key: Arc<Key>,            // Signing and verification key
+    issuer: Option<String>,   // Expected `iss` claim, added when signing
+    audience: Option<String>, // Expected `aud` claim, added when signing
+    leeway: Duration,         // Allowed clock skew for `exp` and `nbf`
+    require_exp: bool,        // Whether tokens without `exp` are rejected
+}
+
+impl Jwt {
+    /// Creates a JWT key using HMAC-SHA256 with a shared secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret`: The secret, at least 32 random bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new Jwt instance that signs and verifies tokens.
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self::with_key(
+            Algorithm::HS256,
+            Key::Hmac(hmac::Key::new(hmac::HMAC_SHA256, secret)),
+        )
+    }
+
+    /// Creates an RS256 key from an RSA private key.
+    ///
+    /// # Arguments
+    ///
+    /// * `private_key`: A PKCS#8 private key (2048 to 8192 bits), PEM (`BEGIN PRIVATE KEY`)
+    ///   or DER encoded.
+    ///
+    /// # Returns
+    ///
+    /// A Jwt instance that signs and verifies tokens, or `JwtError::InvalidKey`.
+    pub fn rs256(private_key: &[u8]) -> Result<Self, JwtError> {
+        let pair = RsaKeyPair::from_pkcs8(&der(private_key)?).map_err(|_| JwtError::InvalidKey)?;
+        let public = pair.public().as_ref().to_vec();
+        Ok(Self::with_key(
+            Algorithm::RS256,
+            Key::Rsa {
+                pair: Some(pair),
+                public,
+            },
+        ))
+    }
+
+    /// Creates an RS256 key that only verifies tokens, from an RSA public key.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key`: A public key (2048 to 8192 bits) in SubjectPublicKeyInfo
+    ///   (`BEGIN PUBLIC KEY`, e.g. from `openssl rsa -pubout`) or PKCS#1
+    ///   (`BEGIN RSA PUBLIC KEY`) format, PEM or DER encoded.
+    ///
+    /// # Returns
+    ///
+    /// A Jwt instance that verifies tokens, or `JwtError::InvalidKey`.
+    pub fn rs256_public(public_key: &[u8]) -> Result<Self, JwtError> {
+        let public = rsa_public_key(&der(public_key)?).ok_or(JwtError::InvalidKey)?;
+        Ok(Self::with_key(
+            Algorithm::RS256,
+            Key::Rsa { pair: None, public },
+        ))
+    }
+
+    /// Creates an EdDSA key from an Ed25519 private key.
+    ///
+    /// # Arguments
+    ///
+    /// * `private_key`: A PKCS#8 private key, PEM (`BEGIN PRIVATE KEY`) or DER encoded.
+    ///
+    /// # Returns
+    ///
+    /// A Jwt instance that signs and verifies tokens, or `JwtError::InvalidKey`.
+    pub fn ed25519(private_key: &[u8]) -> Result<Self, JwtError> {
+        let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der(private_key)?)
+            .map_err(|_| JwtError::InvalidKey)?;
+        let public = pair.public_key().as_ref().to_vec();
+        Ok(Self::with_key(
+            Algorithm::EdDSA,
+            Key::Ed25519 {
+                pair: Some(pair),
+                public,
+            },
+        ))
+    }
+
+    /// Creates an EdDSA key that only verifies tokens, from an Ed25519 public key.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key`: The raw 32-byte public key.
+    ///
+    /// # Returns
+    ///
+    /// A Jwt instance that verifies tokens, or `JwtError::InvalidKey`.
+    pub fn ed25519_public(public_key: &[u8]) -> Result<Self, JwtError> {
+        if public_key.len() != 32 {
+            return Err(JwtError::InvalidKey);
+        }
+        Ok(Self::with_key(
+            Algorithm::EdDSA,
+            Key::Ed25519 {
+                pair: None,
+                public: public_key.to_vec(),
+            },
+        ))
+    }
+
+    fn with_key(algorithm: Algorithm, key: Key) -> Self {
+        Self {
+            algorithm,
+            key: Arc::new(key),
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(60),
+            require_exp: true,
+        }
+    }
+
+    /// Sets the issuer: `sign` adds it as the `iss` claim and `verify` requires it.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer`: The issuer (e.g. "https://auth.example.com").
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Sets the audience: `sign` adds it as the `aud` claim and `verify` requires `aud`
+    /// to contain it.
+    ///
+    /// # Arguments
+    ///
+    /// * `audience`: The audience (e.g. "api").
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Sets the allowed clock skew when checking `exp` and `nbf` (60 seconds by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `leeway`: The allowed skew.
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Sets whether tokens without an `exp` claim are rejected (true by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `require`: Whether `exp` is required.
+    pub fn require_exp(mut self, require: bool) -> Self {
+        self.require_exp = require;
+        self
+    }
+
+    /// Returns the signature algorithm of the key.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Issues a token for the given claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims`: The claims, serialized as a JSON object. The configured issuer and
+    ///   audience are added if the claims do not set `iss` or `aud`.
+    ///
+    /// # Returns
+    ///
+    /// The compact token, or an error if the claims are not a JSON object or the key
+    /// cannot sign.
+    pub fn sign<C: Serialize>(&self, claims: &C) -> Result<String, JwtError> {
+        let mut payload = serde_json::to_value(claims)
+            .map_err(|error| JwtError::InvalidClaims(error.to_string()))?;
+        let object = payload
+            .as_object_mut()
+            .ok_or_else(|| JwtError::InvalidClaims("claims must be a JSON object".to_string()))?;
+        if let Some(issuer) = &self.issuer {
+            object.entry("iss").or_insert(issuer.as_str().into());
+        }
+        if let Some(audience) = &self.audience {
+            object.entry("aud").or_insert(audience.as_str().into());
+        }
+
+        let header = Header {
+            alg: self.algorithm.as_str().to_string(),
+            typ: Some("JWT".to_string()),
+        };
+        let signing_input = format!("{}.{}", encode_json(&header)?, encode_json(&payload)?);
+        let signature = self.signature(signing_input.as_bytes())?;
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature)
+        ))
+    }
+
+    /// Verifies a token and decodes its claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: The compact token.
+    ///
+    /// # Returns
+    ///
+    /// The claims, or the reason the token was rejected.
+    pub fn verify<C: DeserializeOwned>(&self, token: &str) -> Result<C, JwtError> {
+        self.verify_payload(token).and_then(|(payload, _)| {
+            serde_json::from_slice(&payload)
+                .map_err(|error| JwtError::InvalidClaims(error.to_string()))
+        })
+    }
+
+    /// Verifies a token, returning its payload and subject.
+    fn verify_payload(&self, token: &str) -> Result<(Vec<u8>, Option<String>), JwtError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(JwtError::Malformed);
+        };
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| JwtError::Malformed)
+        };
+
+        let header: Header =
+            serde_json::from_slice(&decode(header)?).map_err(|_| JwtError::Malformed)?;
+        if header.alg != self.algorithm.as_str() {
+            return Err(JwtError::AlgorithmMismatch);
+        }
+        let signing_input = &token[..token.len() - signature.len() - 1];
+        self.check_signature(signing_input.as_bytes(), &decode(signature)?)?;
+
+        let payload = decode(payload)?;
+        let registered: Registered =
+            serde_json::from_slice(&payload).map_err(|_| JwtError::Malformed)?;
+        self.validate(&registered)?;
+        Ok((payload, registered.sub))
+    }
+
+    /// Checks the registered claims of a token with a valid signature.
+    fn validate(&self, claims: &Registered) -> Result<(), JwtError> {
+        let now = utc_ts_sec() as f64;
+        let leeway = self.leeway.as_secs_f64();
+        match claims.exp {
+            Some(exp) if now > exp + leeway => return Err(JwtError::Expired),
+            None if self.require_exp => return Err(JwtError::Expired),
+            _ => {}
+        }
+        if claims.nbf.is_some_and(|nbf| now + leeway < nbf) {
+            return Err(JwtError::NotYetValid);
+        }
+        if let Some(issuer) = &self.issuer
+            && claims.iss.as_ref() != Some(issuer)
+        {
+            return Err(JwtError::InvalidIssuer);
+        }
+        if let Some(audience) = &self.audience
+            && !claims
+                .aud
+                .as_ref()
+                .is_some_and(|aud| aud.contains(audience))
+        {
+            return Err(JwtError::InvalidAudience);
+        }
+        Ok(())
+    }
+
+    /// Signs the header and payload of a token.
+    fn signature(&self, message: &[u8]) -> Result<Vec<u8>, JwtError> {
+        match &*self.key {
+            Key::Hmac(key) => Ok(hmac::sign(key, message).as_ref().to_vec()),
+            Key::Rsa { pair, .. } => {
+                let pair = pair.as_ref().ok_or(JwtError::InvalidKey)?;
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(
+                    &signature::RSA_PKCS1_SHA256,
+                    &SystemRandom::new(),
+                    message,
+                    &mut signature,
+                )
+                .map_err(|_| JwtError::InvalidKey)?;
+                Ok(signature)
+            }
+            Key::Ed25519 { pair, .. } => {
+                let pair = pair.as_ref().ok_or(JwtError::InvalidKey)?;
+                Ok(pair.sign(message).as_ref().to_vec())
+            }
+        }
+    }
+
+    /// Checks the signature of a token.
+    fn check_signature(&self, message: &[u8], signature: &[u8]) -> Result<(), JwtError> {
+        let verified = match &*self.key {
+            Key::Hmac(key) => hmac::verify(key, message, signature),
+            Key::Rsa { public, .. } => {
+                UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, public)
+                    .verify(message, signature)
+            }
+            Key::Ed25519 { public, .. } => {
+                UnparsedPublicKey::new(&signature::ED25519, public).verify(message, signature)
+            }
+        };
+        verified.map_err(|_| JwtError::InvalidSignature)
+    }
+}
+
+/// Returns the claims of a request authenticated by `JwtAuth`.
+///
+/// # Arguments
+///
+/// * `req`: The request.
+///
+/// # Returns
+///
+/// The claims, or `None` if the request did not go through a `JwtAuth<C>` middleware
+/// with the same claims type.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Claims {
+///     sub: String,
+///     admin: bool,
+/// }
+///
+/// fn profile(req: Req, _params: Params) -> Reply {
+///     let body = match claims::<Claims>(&req) {
+///         Some(claims) => format!("{} (admin: {})", claims.sub, claims.admin),
+///         None => "anonymous".to_string(),
+///     };
+///     boxed(async move { Ok(Response::new(Body::from(body))) })
+/// }
+/// ```
+pub fn claims<C: Send + Sync + 'static>(req: &Req) -> Option<&C> {
+    req.extensions().get::<Claims<C>>().map(|claims| &claims.0)
+}
+
+/// The claims stored by `JwtAuth`, wrapped so they cannot collide with other extensions
+/// of the same type.
+struct Claims<C>(C);
+
+/// JWT bearer authentication middleware.
+///
+/// Verifies the token of the `Authorization: Bearer` header with a `Jwt` and stores its
+/// claims, of type `C`, in the request extensions (see `claims`). The `sub` claim, if any,
+/// is also stored as the `Principal`. Requests without a valid token get
+/// `401 Unauthorized` with a `WWW-Authenticate: Bearer` challenge describing the error.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Claims {
+///     sub: String,
+/// }
+///
+/// fn orders(req: Req, _params: Params) -> Reply {
+///     let body = format!("Orders of {}", claims::<Claims>(&req).unwrap().sub);
+///     boxed(async move { Ok(Response::new(Body::from(body))) })
+/// }
+///
+/// let jwt = Jwt::hs256(b"a long random secret").audience("api");
+///
+/// let mut app = Fluxor::new();
+/// app.group("/api", |api| {
+///     api.wrap(JwtAuth::<Claims>::new(jwt));
+///     api.route(GET, "/orders", orders);
+/// });
+/// ```
+pub struct JwtAuth<C> {
+    jwt: Jwt,                       // Verification key and rules
+    realm: Option<String>,          // Realm sent in the challenge
+    claims: PhantomData<fn() -> C>, // Type of the claims
+}
+
+impl<C> Clone for JwtAuth<C> {
+    fn clone(&self) -> Self {
+        Self {
+            jwt: self.jwt.clone(),
+            realm: self.realm.clone(),
+            claims: PhantomData,
+        }
+    }
+}
+
+impl<C: DeserializeOwned + Send + Sync + 'static> JwtAuth<C> {
+    /// Creates a JWT authentication middleware.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwt`: The key and validation rules used to verify tokens.
+    ///
+    /// # Returns
+    ///
+    /// A new JwtAuth instance.
+    pub fn new(jwt: Jwt) -> Self {
+        Self {
+            jwt,
+            realm: None,
+            claims: PhantomData,
+        }
+    }
+
+    /// Sets the realm sent in the challenge.
+    ///
+    /// # Arguments
+    ///
+    /// * `realm`: The realm (e.g. "api").
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Verifies a token and decodes its claims and subject.
+    fn authenticate(&self, token: &str) -> Result<(C, Option<String>), JwtError> {
+        let (payload, subject) = self.jwt.verify_payload(token)?;
+        let claims = serde_json::from_slice(&payload)
+            .map_err(|error| JwtError::InvalidClaims(error.to_string()))?;
+        Ok((claims, subject))
+    }
+}
+
+impl<C: DeserializeOwned + Send + Sync + 'static> Middleware for JwtAuth<C> {
+    fn handle(&self, mut req: Req, params: Params, next: Next) -> Reply {
+        let result = scheme_value(&req, "Bearer").map(|token| self.authenticate(token));
+        match result {
+            Some(Ok((claims, subject))) => {
+                req.extensions_mut().insert(Claims(claims));
+                if let Some(name) = subject {
+                    req.extensions_mut().insert(Principal {
+                        name,
+                        scheme: "Bearer",
+                    });
+                }
+                next.run(req, params)
+            }
+            _ => {
+                let mut challenge = "Bearer".to_string();
+                let mut separator = " ";
+                if let Some(realm) = &self.realm {
+                    challenge.push_str(&format!(r#"{}realm="{}""#, separator, quote(realm)));
+                    separator = ", ";
+                }
+                if let Some(Err(error)) = &result {
+                    challenge.push_str(&format!(
+                        r#"{}error="invalid_token", error_description="{}""#,
+                        separator,
+                        error_description(error)
+                    ));
+                }
+                unauthorized(&req, challenge)
+            }
+        }
+    }
+}
+
+/// Describes a verification error in a challenge, without details of the claims.
+fn error_description(error: &JwtError) -> &'static str {
+    match error {
+        JwtError::Expired => "The token expired",
+        JwtError::NotYetValid => "The token is not valid yet",
+        JwtError::InvalidIssuer | JwtError::InvalidAudience => {
+            "The token was issued for another service"
+        }
+        _ => "The token is invalid",
+    }
+}
+
+/// Serializes a value as base64url-encoded JSON.
+fn encode_json<T: Serialize>(value: &T) -> Result<String, JwtError> {
+    let json =
+        serde_json::to_vec(value).map_err(|error| JwtError::InvalidClaims(error.to_string()))?;
+    Ok(URL_SAFE_NO_PAD.encode(json))
+}
+
+/// DER encoding of the rsaEncryption OID (1.2.840.113549.1.1.1).
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+/// Returns the PKCS#1 `RSAPublicKey` of a PKCS#1 or SubjectPublicKeyInfo public key, or
+/// `None` if it is not a well-formed RSA key of 2048 to 8192 bits.
+fn rsa_public_key(key: &[u8]) -> Option<Vec<u8>> {
+    let (sequence, rest) = der_element(key, 0x30)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let pkcs1 = if der_element(sequence, 0x02).is_some() {
+        key // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+    } else {
+        // SubjectPublicKeyInfo ::= SEQUENCE { AlgorithmIdentifier, BIT STRING }
+        let (algorithm, rest) = der_element(sequence, 0x30)?;
+        let (oid, _) = der_element(algorithm, 0x06)?;
+        let (bits, rest) = der_element(rest, 0x03)?;
+        if oid != RSA_ENCRYPTION_OID || !rest.is_empty() {
+            return None;
+        }
+        bits.strip_prefix(&[0])?
+    };
+
+    let (sequence, rest) = der_element(pkcs1, 0x30)?;
+    let (modulus, exponent) = der_element(sequence, 0x02)?;
+    let (exponent, tail) = der_element(exponent, 0x02)?;
+    if !rest.is_empty() || !tail.is_empty() || exponent.is_empty() {
+        return None;
+    }
+    let modulus = match modulus.iter().position(|&byte| byte != 0) {
+        Some(start) => &modulus[start..],
+        None => return None,
+    };
+    let bits = modulus.len() * 8 - modulus[0].leading_zeros() as usize;
+    (2048..=8192).contains(&bits).then(|| pkcs1.to_vec())
+}
+
+/// Reads a DER element with the given tag, returning its contents and the bytes after it.
+fn der_element(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&first, rest) = input.split_first()?;
+    let (&length, mut rest) = rest.split_first()?;
+    if first != tag {
+        return None;
+    }
+    let length = if length < 0x80 {
+        length as usize
+    } else {
+        let count = (length & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let (bytes, tail) = rest.split_at(count);
+        rest = tail;
+        bytes
+            .iter()
+            .fold(0usize, |length, &byte| (length << 8) | byte as usize)
+    };
+    (rest.len() >= length).then(|| rest.split_at(length))
+}
+
+/// Decodes a PEM key into DER, or returns DER input unchanged.
+fn der(key: &[u8]) -> Result<Vec<u8>, JwtError> {
+    let Some(pem) = std::str::from_utf8(key)
+        .ok()
+        .filter(|text| text.trim_start().starts_with("-----BEGIN"))
+    else {
+        return Ok(key.to_vec());
+    };
+    let body: String = pem
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect();
+    STANDARD.decode(body).map_err(|_| JwtError::InvalidKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::middleware::run_middleware;
+    use crate::core::{boxed, principal};
+    use fluxio::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use fluxio::{Body, Request, Response, StatusCode};
+    use ring::rand::SystemRandom;
+    use serde_json::{Value, json};
+
+    const RSA_PRIVATE: &[u8] = include_bytes!("testdata/rsa_private.pem");
+    const RSA_PUBLIC: &[u8] = include_bytes!("testdata/rsa_public.pem");
+    const RSA_PUBLIC_PKCS1: &[u8] = include_bytes!("testdata/rsa_public_pkcs1.pem");
+
+    fn hs256() -> Jwt {
+        Jwt::hs256(b"0123456789abcdef0123456789abcdef")
+    }
+
+    fn now() -> u64 {
+        utc_ts_sec()
+    }
+
+    fn verify(jwt: &Jwt, token: &str) -> Result<Value, JwtError> {
+        jwt.verify(token)
+    }
+
+    /// Replaces one of the three parts of a token.
+    fn replace_part(token: &str, index: usize, part: &str) -> String {
+        let mut parts: Vec<&str> = token.split('.').collect();
+        parts[index] = part;
+        parts.join(".")
+    }
+
+    #[test]
+    fn hs256_round_trip() {
+        let jwt = hs256();
+        let token = jwt
+            .sign(&json!({"sub": "alice", "exp": now() + 60}))
+            .unwrap();
+        assert_eq!(verify(&jwt, &token).unwrap()["sub"], "alice");
+        assert_eq!(
+            verify(&Jwt::hs256(b"another secret"), &token),
+            Err(JwtError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rs256_round_trip_with_public_keys() {
+        let jwt = Jwt::rs256(RSA_PRIVATE).unwrap();
+        let token = jwt
+            .sign(&json!({"sub": "alice", "exp": now() + 60}))
+            .unwrap();
+        assert_eq!(verify(&jwt, &token).unwrap()["sub"], "alice");
+
+        for public in [RSA_PUBLIC, RSA_PUBLIC_PKCS1] {
+            let verifier = Jwt::rs256_public(public).unwrap();
+            assert_eq!(verify(&verifier, &token).unwrap()["sub"], "alice");
+            assert_eq!(
+                verifier.sign(&json!({"exp": now() + 60})),
+                Err(JwtError::InvalidKey)
+            );
+        }
+    }
+
+    #[test]
+    fn rs256_public_rejects_invalid_keys() {
+        assert!(Jwt::rs256_public(RSA_PRIVATE).is_err());
+        assert!(Jwt::rs256_public(b"not a key").is_err());
+        assert!(
+            Jwt::rs256_public(b"-----BEGIN PUBLIC KEY-----\nAAAA\n-----END PUBLIC KEY-----")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn eddsa_round_trip_with_public_key() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let jwt = Jwt::ed25519(pkcs8.as_ref()).unwrap();
+        let token = jwt.sign(&json!({"sub": "bob", "exp": now() + 60})).unwrap();
+        assert_eq!(verify(&jwt, &token).unwrap()["sub"], "bob");
+
+        let public = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .unwrap()
+            .public_key()
+            .as_ref()
+            .to_vec();
+        let verifier = Jwt::ed25519_public(&public).unwrap();
+        assert_eq!(verify(&verifier, &token).unwrap()["sub"], "bob");
+        assert_eq!(
+            Jwt::ed25519_public(&public[1..]).err(),
+            Some(JwtError::InvalidKey)
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let jwt = hs256();
+        let token = jwt
+            .sign(&json!({"sub": "alice", "exp": now() + 60}))
+            .unwrap();
+
+        let forged_payload =
+            URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"admin","exp":{}}}"#, now() + 60));
+        assert_eq!(
+            verify(&jwt, &replace_part(&token, 1, &forged_payload)),
+            Err(JwtError::InvalidSignature)
+        );
+        let signature = URL_SAFE_NO_PAD.encode([0u8; 32]);
+        assert_eq!(
+            verify(&jwt, &replace_part(&token, 2, &signature)),
+            Err(JwtError::InvalidSignature)
+        );
+        assert_eq!(verify(&jwt, "a.b"), Err(JwtError::Malformed));
+        assert_eq!(
+            verify(&jwt, &format!("{}.extra", token)),
+            Err(JwtError::Malformed)
+        );
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let token = hs256().sign(&json!({"exp": now() + 60})).unwrap();
+        let rsa = Jwt::rs256_public(RSA_PUBLIC).unwrap();
+        assert_eq!(verify(&rsa, &token), Err(JwtError::AlgorithmMismatch));
+
+        let none = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let unsigned = replace_part(&replace_part(&token, 0, &none), 2, "");
+        assert_eq!(
+            verify(&hs256(), &unsigned),
+            Err(JwtError::AlgorithmMismatch)
+        );
+    }
+
+    #[test]
+    fn checks_expiration_with_leeway() {
+        let jwt = hs256().leeway(Duration::from_secs(30));
+        let recent = jwt.sign(&json!({"exp": now() - 10})).unwrap();
+        assert!(verify(&jwt, &recent).is_ok());
+        let expired = jwt.sign(&json!({"exp": now() - 120})).unwrap();
+        assert_eq!(verify(&jwt, &expired), Err(JwtError::Expired));
+        assert_eq!(
+            verify(&jwt.clone().leeway(Duration::ZERO), &recent),
+            Err(JwtError::Expired)
+        );
+
+        let no_exp = jwt.sign(&json!({"sub": "alice"})).unwrap();
+        assert_eq!(verify(&jwt, &no_exp), Err(JwtError::Expired));
+        assert!(verify(&jwt.clone().require_exp(false), &no_exp).is_ok());
+    }
+
+    #[test]
+    fn checks_not_before_with_leeway() {
+        let jwt = hs256().leeway(Duration::from_secs(30));
+        let soon = jwt
+            .sign(&json!({"exp": now() + 600, "nbf": now() + 10}))
+            .unwrap();
+        assert!(verify(&jwt, &soon).is_ok());
+        let later = jwt
+            .sign(&json!({"exp": now() + 600, "nbf": now() + 120}))
+            .unwrap();
+        assert_eq!(verify(&jwt, &later), Err(JwtError::NotYetValid));
+    }
+
+    #[test]
+    fn checks_issuer_and_audience() {
+        let jwt = hs256().issuer("https://auth.example.com").audience("api");
+        let token = jwt.sign(&json!({"exp": now() + 60})).unwrap();
+        let payload = verify(&jwt, &token).unwrap();
+        assert_eq!(payload["iss"], "https://auth.example.com");
+        assert_eq!(payload["aud"], "api");
+
+        let other_issuer = hs256()
+            .sign(&json!({"exp": now() + 60, "iss": "https://evil.example.com", "aud": "api"}))
+            .unwrap();
+        assert_eq!(verify(&jwt, &other_issuer), Err(JwtError::InvalidIssuer));
+        let no_issuer = hs256()
+            .sign(&json!({"exp": now() + 60, "aud": "api"}))
+            .unwrap();
+        assert_eq!(verify(&jwt, &no_issuer), Err(JwtError::InvalidIssuer));
+
+        let other_audience = hs256()
+            .sign(&json!({"exp": now() + 60, "iss": "https://auth.example.com", "aud": "web"}))
+            .unwrap();
+        assert_eq!(
+            verify(&jwt, &other_audience),
+            Err(JwtError::InvalidAudience)
+        );
+        let audiences = hs256().sign(&json!({"exp": now() + 60, "iss": "https://auth.example.com", "aud": ["web", "api"]})).unwrap();
+        assert!(verify(&jwt, &audiences).is_ok());
+    }
+
+    fn echo_claims(req: Req, _params: Params) -> Reply {
+        let body = format!(
+            "{:?} {:?} {:?}",
+            claims::<Value>(&req).map(|claims| &claims["sub"]),
+            principal(&req).map(|principal| &principal.name),
+            req.extensions().get::<Value>()
+        );
+        boxed(async move { Ok(Response::new(Body::from(body))) })
+    }
+
+    async fn call(token: Option<&str>) -> Response<Body> {
+        let mut req = Request::new(Body::empty());
+        req.extensions_mut().insert(json!("unrelated"));
+        if let Some(token) = token {
+            req.headers_mut()
+                .insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        }
+        let auth = JwtAuth::<Value>::new(hs256()).realm("api");
+        run_middleware(auth, req, echo_claims).await
+    }
+
+    #[tokio::test]
+    async fn middleware_exposes_claims() {
+        let token = hs256()
+            .sign(&json!({"sub": "alice", "exp": now() + 60}))
+            .unwrap();
+        let response = call(Some(&token)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = fluxio::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            r#"Some(String("alice")) Some("alice") Some(String("unrelated"))"#
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware_challenges_invalid_tokens() {
+        let response = call(None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="api""#
+        );
+
+        let expired = hs256()
+            .sign(&json!({"sub": "alice", "exp": now() - 3600}))
+            .unwrap();
+        let response = call(Some(&expired)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="api", error="invalid_token", error_description="The token expired""#
+        );
+    }
+}
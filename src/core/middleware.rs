@@ -0,0 +1,151 @@
+use super::{Handler, Params, Reply, Req};
+use std::sync::Arc;
+
+/// A layer of the request pipeline, running code before and after the rest of the chain.
+///
+/// A middleware receives the request, its parameters and a `Next` handle to the rest of
+/// the chain. It can modify the request or parameters before calling `next.run`, modify
+/// the response after it, or short-circuit by returning its own response without calling
+/// `next` at all.
+///
+/// Middleware can be attached globally with `Fluxor::wrap` (around route dispatch, static
+/// file serving and the 404/405 fallbacks), per group by calling `wrap` on a mounted
+/// application (see `Fluxor::mount`), or per route with `Route::wrap`. Global middleware
+/// runs first, then group middleware, then route middleware; within each level, in the
+/// order it was added.
+///
+/// Closures with the signature `Fn(Req, Params, Next) -> Reply` implement this trait.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn hello(_req: Req, _params: Params) -> Reply {
+///     boxed(async { Ok(Response::new(Body::from("Hello"))) })
+/// }
+///
+/// let mut app = Fluxor::new();
+/// app.route(GET, "/", hello);
+///
+/// // Add a header to every response
+/// app.wrap(|req: Req, params: Params, next: Next| {
+///     boxed(async move {
+///         let mut response = next.run(req, params).await?;
+///         response.headers_mut().insert("X-Powered-By", "Fluxor".parse().unwrap());
+///         Ok(response)
+///     })
+/// });
+///
+/// // Reject requests without an API key
+/// app.wrap(|req: Req, params: Params, next: Next| {
+///     if !req.headers().contains_key("X-Api-Key") {
+///         return boxed(async {
+///             Ok(Response::builder()
+///                 .status(StatusCode::UNAUTHORIZED)
+///                 .body(Body::from("Missing API key"))
+///                 .unwrap())
+///         });
+///     }
+///     next.run(req, params)
+/// });
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    /// Handles a request, calling `next.run(req, params)` to continue the chain.
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply;
+
+    /// Returns the name of the middleware, shown in the route table.
+    ///
+    /// Defaults to the name of the type (or function) without its module path.
+    fn name(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        match name
+            .split('<')
+            .next()
+            .and_then(|path| path.rsplit("::").next())
+        {
+            Some("{{closure}}") => "closure".to_string(),
+            Some(short) if !short.is_empty() => short.to_string(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Req, Params, Next) -> Reply + Send + Sync + 'static,
+{
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply {
+        self(req, params, next)
+    }
+}
+
+/// The rest of a middleware chain, ending with the handler (or the router for global middleware).
+#[derive(Clone)]
+pub struct Next {
+    chain: Arc<[Arc<dyn Middleware>]>, // Middleware of the chain, outermost first
+    index: usize,                      // Position of the next middleware to run
+    endpoint: Handler,                 // Called once every middleware has run
+}
+
+impl Next {
+    /// Creates a chain running the middleware in order, then the endpoint.
+    pub(crate) fn new(chain: Arc<[Arc<dyn Middleware>]>, endpoint: Handler) -> Self {
+        Self {
+            chain,
+            index: 0,
+            endpoint,
+        }
+    }
+
+    /// Runs the rest of the chain with the given request and parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `req`: The request, possibly modified by the current middleware.
+    /// * `params`: The parameters, possibly modified by the current middleware.
+    ///
+    /// # Returns
+    ///
+    /// A boxed future resolving to the response of the rest of the chain.
+    pub fn run(self, req: Req, params: Params) -> Reply {
+        match self.chain.get(self.index).cloned() {
+            Some(middleware) => {
+                let next = Next {
+                    index: self.index + 1,
+                    ..self
+                };
+                middleware.handle(req, params, next)
+            }
+            None => (self.endpoint)(req, params),
+        }
+    }
+}
+
+/// Runs a middleware in front of an endpoint and returns the response, for unit tests.
+#[cfg(test)]
+pub(crate) async fn run_middleware<M, F>(
+    middleware: M,
+    req: Req,
+    endpoint: F,
+) -> fluxio::Response<fluxio::Body>
+where
+    M: Middleware,
+    F: Fn(Req, Params) -> Reply + Send + Sync + 'static,
+{
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    let chain: Arc<[Arc<dyn Middleware>]> = Arc::new([Arc::new(middleware) as Arc<dyn Middleware>]);
+    let params = Params {
+        dir: Arc::new(PathBuf::new()),
+        extra: HashMap::new(),
+        custom_404: Arc::new(String::new()),
+        urls: Arc::new(HashMap::new()),
+        raw: HashMap::new(),
+    };
+    match Next::new(chain, Arc::new(endpoint)).run(req, params).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
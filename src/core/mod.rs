@@ -1,4 +1,32 @@
+mod access_log;
+mod auth;
+mod compression;
+mod cors;
+mod csrf;
+mod introspect;
+mod jwt;
+mod middleware;
+mod rate_limit;
+mod recover;
+mod request_id;
+mod router;
+
+pub use access_log::{AccessLog, LogFormat};
+pub use auth::{principal, BasicAuth, BearerAuth, CredentialsClosure, Principal, TokenClosure};
+pub use compression::{Compression, Encoding};
+pub use cors::{AllowOrigin, Cors};
+pub use csrf::{csrf_field, csrf_token, Csrf, CsrfToken};
+pub use introspect::RouteInfo;
+pub use jwt::{claims, Algorithm, Jwt, JwtAuth, JwtError};
+pub use middleware::{Middleware, Next};
+pub use rate_limit::{KeyClosure, KeyValidator, MemoryStore, Quota, RateLimit, RateLimitDecision, RateLimitKey, RateLimitStore, StoreFuture};
+pub use request_id::{request_id, RequestId};
+pub use router::{RouteConflict, TrailingSlash};
+
 use dotenv::dotenv;
+use fluxio::body::{to_bytes, HttpBody};
+use fluxio::header::HeaderName;
+use fluxio::server::conn::AddrStream;
 use fluxio::service::{make_service_fn, service_fn};
 use fluxio::{Body, Request, Response, Server, StatusCode};
 use std::collections::HashMap;
@@ -8,7 +36,9 @@ use std::future::Future;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs as async_fs;
 use wtime::{
     calc::get_day_name,
@@ -17,9 +47,16 @@ use wtime::{
 
 use styledlog::Colorize;
 use cans::mime::{set_mime_types, insert_mime_type, remove_mime_type};
+use router::{Lookup, Router, Segment};
 
 pub type Req = fluxio::Request<fluxio::Body>;
 pub type Reply = Pin<Box<dyn Future<Output = Result<fluxio::Response<fluxio::Body>, std::convert::Infallible>> + Send>>;
+pub type Handler = Arc<dyn Fn(Req, Params) -> Reply + Send + Sync>; // Route handler (function or closure)
+pub type ResponseClosure = Arc<dyn Fn(&str) -> String + Send + Sync>; // Builds a response body for a content type
+
+/// The peer address of a request's connection, stored in the request extensions (see `remote_addr`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RemoteAddr(pub SocketAddr);
 
 /// Parameters for the Fluxor application including the directory and any extra parameters.
 #[derive(Clone)]
@@ -27,6 +64,36 @@ pub struct Params {
     pub dir: Arc<PathBuf>,              // Directory path (required for application)
     pub extra: HashMap<String, String>, // Required and optional parameters
     pub custom_404: Arc<String>,
+    pub urls: Arc<HashMap<String, String>>, // Paths of named routes, used by `url_for`
+    pub raw: HashMap<String, String>,   // Captured path parameters before percent-decoding
+}
+
+impl Params {
+    /// Generates the URL path of a named route (see `Fluxor::route_named`).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `name`: The name of the route.
+    /// * `params`: Values for the route's path parameters; they are percent-encoded.
+    /// 
+    /// # Returns
+    /// 
+    /// The URL path, or `None` if no route has that name or a parameter is missing
+    /// or does not satisfy its constraint.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn edit_link(_req: Req, params: Params) -> Reply {
+    ///     let link = params.url_for("user_edit", &[("id", "42")]).unwrap_or_default();
+    ///     boxed(async move { Ok(Response::new(Body::from(link))) }) // "/users/42/edit"
+    /// }
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
+        router::build_url(self.urls.get(name)?, params)
+    }
 }
 
 /// Struct representing a route in the web server.
@@ -34,12 +101,104 @@ pub struct Params {
 pub struct Route {
     pub method: fluxio::Method,                         // HTTP method (GET, POST, etc.)
     pub path: String,                                   // Path for the route
-    pub handler: fn(Request<Body>, Params) -> Reply,    // Handler function associated with the route
+    pub handler: Handler,                               // Handler associated with the route
+    pub auto_head: bool,                                // Answer HEAD with this GET route
+    pub auto_options: bool,                             // Answer OPTIONS for this route's path
+    pub name: Option<String>,                           // Name used for reverse URL generation
+    pub host: Option<String>,                           // Host pattern the request must match
+    pub middleware: Vec<Arc<dyn Middleware>>,           // Middleware wrapping the handler, outermost first
+    pub timeout: Option<Duration>,                      // Time limit of the handler, overriding the global one
+    pub body_limit: Option<u64>,                        // Maximum request body size, overriding the global one
 }
 
 impl Route {
+    /// Enables or disables answering HEAD requests with this GET route (enabled by default).
+    /// 
+    /// The GET handler runs and its body is dropped, keeping the `Content-Length` header.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `enabled`: Whether HEAD requests are served by this route.
+    pub fn auto_head(&mut self, enabled: bool) -> &mut Self {
+        self.auto_head = enabled;
+        self
+    }
+
+    /// Restricts the route to requests whose `Host` matches a pattern.
+    /// 
+    /// Labels are matched case-insensitively and the port is ignored. A label may be a
+    /// parameter, e.g. `<tenant>.example.com`, captured into `Params::extra` like path
+    /// parameters. Host-restricted routes take precedence over unrestricted routes with
+    /// the same path.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `pattern`: The host pattern (e.g. "api.example.com").
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `pattern` is not a valid host pattern.
+    pub fn host(&mut self, pattern: &str) -> &mut Self {
+        if let Err(e) = router::parse_host(pattern) {
+            panic!("Invalid route host '{}': {}", pattern, e);
+        }
+        self.host = Some(pattern.to_string());
+        self
+    }
+
+    /// Adds a middleware running around this route's handler only.
+    /// 
+    /// Route middleware runs after global and group middleware, in the order it was added.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `middleware`: The middleware (see `Middleware`).
+    pub fn wrap<M: Middleware>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sets the time limit of this route's handler, overriding `Fluxor::set_timeout`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `duration`: The time limit, covering the route middleware and the handler.
+    pub fn timeout(&mut self, duration: Duration) -> &mut Self {
+        self.timeout = Some(duration);
+        self
+    }
+
+    /// Sets the maximum request body size of this route, overriding `Fluxor::set_body_limit`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `bytes`: The maximum body size in bytes.
+    pub fn body_limit(&mut self, bytes: u64) -> &mut Self {
+        self.body_limit = Some(bytes);
+        self
+    }
+
+    /// Enables or disables automatic OPTIONS answers for this route (enabled by default).
+    /// 
+    /// OPTIONS requests to a path with no explicit OPTIONS route get `204 No Content`
+    /// with an `Allow` header listing the methods registered for the path.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `enabled`: Whether OPTIONS requests are answered for this route.
+    pub fn auto_options(&mut self, enabled: bool) -> &mut Self {
+        self.auto_options = enabled;
+        self
+    }
+
     /// Matches the incoming request path with the route path and captures parameters.
     /// 
+    /// Typed parameters such as `<id:int>`, `<ratio:float>`, `<uuid:uuid>` or
+    /// `<slug:re([a-z-]+)>` only match segments satisfying their constraint. A trailing
+    /// wildcard such as `<path..>` captures the rest of the path. Captured values are
+    /// percent-decoded; segments that are not valid UTF-8 once decoded do not match.
+    /// If the route has a host pattern (see `host`), the request host must match it too.
+    /// 
     /// # Arguments
     /// 
     /// * `req`: The incoming request.
@@ -48,24 +207,43 @@ impl Route {
     /// 
     /// Option containing a HashMap of captured parameters if the route matches, else None.
     pub fn is_match(&self, req: &Req) -> Option<HashMap<String, String>> {
-        let path_segments: Vec<&str> = self.path.split('/').collect();
-        let request_segments: Vec<&str> = req.uri().path().split('/').collect();
+        let path_segments = router::parse_pattern(&self.path).ok()?;
+        let request_segments = router::split_path(req.uri().path());
 
-        if path_segments.len() != request_segments.len() {
+        let wildcard = matches!(path_segments.last(), Some(Segment::Wildcard(_)));
+
+        if path_segments.len() != request_segments.len()
+            && !(wildcard && request_segments.len() >= path_segments.len())
+        {
             return None; // Path not a match
         }
 
         let mut params = HashMap::new();
 
-        for (route_segment, request_segment) in path_segments.iter().zip(request_segments.iter()) {
-            if route_segment.starts_with('<') && route_segment.ends_with('>') {
-                let key = route_segment
-                    .trim_matches('<')
-                    .trim_matches('>')
-                    .to_string();
-                params.insert(key, request_segment.to_string());
-            } else if route_segment != request_segment {
-                return None; // Path segments do not match
+        if let Some(pattern) = &self.host {
+            let pattern = router::parse_host(pattern).ok()?;
+            params.extend(router::match_host(&pattern, &request_host(req)?)?);
+        }
+
+        for (index, (route_segment, request_segment)) in path_segments.iter().zip(request_segments.iter()).enumerate() {
+            match route_segment {
+                Segment::Wildcard(key) => {
+                    let tail = request_segments[index..].join("/");
+                    let value = router::decode(&tail).filter(|value| router::is_valid_tail(value))?;
+                    params.insert(key.clone(), value.into_owned());
+                }
+                Segment::Param(key, constraint) => {
+                    let value = router::decode(request_segment)?;
+                    if value.is_empty() || !constraint.matches(&value) {
+                        return None; // Segment does not satisfy the parameter constraint
+                    }
+                    params.insert(key.clone(), value.into_owned());
+                }
+                Segment::Static(literal) => {
+                    if literal != request_segment {
+                        return None; // Path segments do not match
+                    }
+                }
             }
         }
 
@@ -78,7 +256,65 @@ pub struct Fluxor {
     pub params: Params,                         // Parameters for the server
     pub routes: Vec<Route>,                     // List of routes
     pub mime_types: HashMap<String, String>,    // Store MIME types
-    pub custom_404_closure: Option<Arc<dyn Fn(&str) -> String + Send + Sync>>, // Closure for dynamic 404
+    pub custom_404_closure: Option<ResponseClosure>, // Closure for dynamic 404
+    pub group_404_closures: Vec<(String, ResponseClosure)>, // Closures for dynamic 404 under mounted prefixes
+    pub custom_405_closure: Option<ResponseClosure>, // Closure for dynamic 405
+    pub strict_routes: bool,                    // Refuse to start when routes conflict
+    pub trailing_slash: TrailingSlash,          // Trailing-slash policy
+    pub route_table_path: Option<String>,       // Path serving the route table as JSON (debug builds)
+    pub host: Option<String>,                   // Host pattern applied to routes without one
+    pub middleware: Vec<Arc<dyn Middleware>>,   // Middleware wrapping every request, outermost first
+    pub request_id_header: Option<String>,      // Header carrying request IDs, if enabled
+    pub timeout: Option<Duration>,              // Time limit of handlers without their own
+    pub timeout_status: StatusCode,             // Status of timed out requests (503 or 504)
+    pub custom_timeout_closure: Option<ResponseClosure>, // Closure for dynamic timeout responses
+    pub custom_500_closure: Option<ResponseClosure>, // Closure for dynamic 500
+    pub body_limit: Option<u64>,                // Maximum request body size of routes without their own
+}
+
+/// Entry point of every request once the server is running.
+struct Pipeline {
+    chain: Next,                            // Global middleware around `handle_request`
+    params: Params,                         // Parameters for the server
+    request_id_header: Option<HeaderName>,  // Header carrying request IDs, if enabled
+    custom_500_closure: Option<ResponseClosure>, // Closure for dynamic 500
+}
+
+/// Compiled server state shared by every connection once the server is running.
+struct App {
+    params: Params,                             // Parameters for the server
+    routes: Vec<Route>,                         // Registered routes, indexed by the router
+    router: Router,                             // Routing tree built from `routes`
+    mime_types: HashMap<String, String>,        // Store MIME types
+    custom_404_closure: Option<ResponseClosure>, // Closure for dynamic 404
+    group_404_closures: Vec<(String, ResponseClosure)>, // Closures for dynamic 404 under mounted prefixes
+    custom_405_closure: Option<ResponseClosure>, // Closure for dynamic 405
+    trailing_slash: TrailingSlash,              // Trailing-slash policy
+    handlers: Vec<Next>,                        // Route handlers wrapped in their middleware, indexed like `routes`
+    timeout: Option<Duration>,                  // Time limit of handlers without their own
+    timeout_status: StatusCode,                 // Status of timed out requests
+    custom_timeout_closure: Option<ResponseClosure>, // Closure for dynamic timeout responses
+    custom_500_closure: Option<ResponseClosure>, // Closure for dynamic 500
+    body_limit: Option<u64>,                    // Maximum request body size of routes without their own
+}
+
+impl App {
+    /// Returns the 404 closure for a request path: the one of the innermost mounted
+    /// group containing the path, or the application-wide one.
+    fn custom_404_for(&self, path: &str) -> Option<&ResponseClosure> {
+        self.group_404_closures
+            .iter()
+            .filter(|(prefix, _)| has_prefix(path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, closure)| closure)
+            .or(self.custom_404_closure.as_ref())
+    }
+}
+
+impl Default for Fluxor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Fluxor {
@@ -94,10 +330,25 @@ impl Fluxor {
                 dir: Arc::new(PathBuf::new()),
                 extra: HashMap::new(),
                 custom_404: Arc::new(r#"<html><body><h1>404 Not Found</h1><p>404 Page Not Found.</p></body></html>"#.to_string()),
+                urls: Arc::new(HashMap::new()),
+                raw: HashMap::new(),
             },
             routes: Vec::new(),
             mime_types, // Set the initialized MIME types
             custom_404_closure: None,
+            group_404_closures: Vec::new(),
+            custom_405_closure: None,
+            strict_routes: false,
+            trailing_slash: TrailingSlash::Strict,
+            route_table_path: None,
+            host: None,
+            middleware: Vec::new(),
+            request_id_header: None,
+            timeout: None,
+            timeout_status: StatusCode::SERVICE_UNAVAILABLE,
+            custom_timeout_closure: None,
+            custom_500_closure: None,
+            body_limit: None,
         }
     }
 
@@ -108,6 +359,133 @@ impl Fluxor {
         self.custom_404_closure = Some(Arc::new(closure));
     }
 
+    /// Sets a closure generating the body of 405 Method Not Allowed responses.
+    /// 
+    /// A 405 is returned when the request path matches a route registered for other
+    /// methods; the `Allow` header listing those methods is always set by Fluxor.
+    /// The closure receives the negotiated content type, as with `set_custom_404`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `closure`: A closure taking the content type and returning the response body.
+    pub fn set_custom_405<F>(&mut self, closure: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.custom_405_closure = Some(Arc::new(closure));
+    }
+
+    /// Sets a closure generating the body of 500 Internal Server Error responses.
+    /// 
+    /// A 500 is returned when a handler or middleware panics; the panic is logged with
+    /// the request method, path and ID. The closure receives the negotiated content type,
+    /// as with `set_custom_404`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `closure`: A closure taking the content type and returning the response body.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.set_custom_500(|content_type| match content_type {
+    ///     "application/json" => r#"{"error": "Something went wrong."}"#.to_string(),
+    ///     _ => "Something went wrong.".to_string(),
+    /// });
+    /// ```
+    pub fn set_custom_500<F>(&mut self, closure: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.custom_500_closure = Some(Arc::new(closure));
+    }
+
+    /// Sets the default maximum request body size of routes.
+    /// 
+    /// Requests declaring a larger `Content-Length` are rejected with 413 Payload Too Large
+    /// before the handler runs. Bodies without a declared length are counted while they
+    /// stream in: once the limit is exceeded, reading the body fails in the handler and
+    /// the client gets a 413 whatever the handler returns. On a mounted application (see
+    /// `mount`), it only applies to that application's routes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `bytes`: The maximum body size in bytes.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn upload(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("uploaded"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.set_body_limit(1024 * 1024); // 1 MiB
+    /// app.route(POST, "/upload", upload).body_limit(100 * 1024 * 1024); // 100 MiB
+    /// ```
+    pub fn set_body_limit(&mut self, bytes: u64) {
+        self.body_limit = Some(bytes);
+    }
+
+    /// Sets the default time limit of route handlers.
+    /// 
+    /// A handler still running when its time limit (this one, or the one set with
+    /// `Route::timeout`) expires is cancelled: its future is dropped, and the client gets
+    /// a 503 Service Unavailable response (see `set_timeout_status` and `set_custom_timeout`).
+    /// Tasks spawned by the handler are not cancelled. On a mounted application (see
+    /// `mount`), it only applies to that application's routes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `duration`: The time limit, covering the route middleware and the handler.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// use std::time::Duration;
+    /// 
+    /// fn report(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("report"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.set_timeout(Duration::from_secs(5));
+    /// app.set_timeout_status(StatusCode::GATEWAY_TIMEOUT);
+    /// app.route(GET, "/report", report).timeout(Duration::from_secs(30));
+    /// ```
+    pub fn set_timeout(&mut self, duration: Duration) {
+        self.timeout = Some(duration);
+    }
+
+    /// Sets the status of responses to timed out requests.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `status`: `StatusCode::SERVICE_UNAVAILABLE` (503, default) or `StatusCode::GATEWAY_TIMEOUT` (504).
+    pub fn set_timeout_status(&mut self, status: StatusCode) {
+        self.timeout_status = status;
+    }
+
+    /// Sets a closure generating the body of responses to timed out requests.
+    /// 
+    /// The closure receives the negotiated content type, as with `set_custom_404`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `closure`: A closure taking the content type and returning the response body.
+    pub fn set_custom_timeout<F>(&mut self, closure: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.custom_timeout_closure = Some(Arc::new(closure));
+    }
+
     /// Sets the directory for static file serving.
     /// 
     /// # Arguments
@@ -122,19 +500,425 @@ impl Fluxor {
     /// # Arguments
     /// 
     /// * `method`: The HTTP method for the route (GET, POST, etc.).
-    /// * `path`: The path for the route.
-    /// * `handler`: A function that handles requests to that route.
-    pub fn route(
-        &mut self,
-        method: fluxio::Method,
-        path: &str,
-        handler: fn(Request<Body>, Params) -> Reply,
-    ) {
+    /// * `path`: The path for the route. Parameters are written as `<name>` and may carry a
+    ///   constraint: `<id:int>`, `<ratio:float>`, `<uuid:uuid>` or `<slug:re([a-z-]+)>`.
+    ///   A final `<name..>` segment captures the rest of the path (e.g. `/files/<path..>`).
+    /// * `handler`: A function or closure that handles requests to that route. Closures may
+    ///   capture state such as a database pool or configuration.
+    /// 
+    /// # Returns
+    /// 
+    /// The registered route, for further configuration (e.g. `auto_head(false)`).
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `path` is not a valid route pattern, e.g. it uses an unknown
+    /// parameter constraint or an invalid `re(...)` expression.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// use std::sync::Arc;
+    /// 
+    /// let greeting = Arc::new(String::from("Hello"));
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.route(GET, "/hello/<name>", move |_req, params| {
+    ///     let body = format!("{}, {}!", greeting, params.extra["name"]);
+    ///     async move { Ok(Response::new(Body::from(body))) }
+    /// });
+    /// ```
+    pub fn route<H, F>(&mut self, method: fluxio::Method, path: &str, handler: H) -> &mut Route
+    where
+        H: Fn(Request<Body>, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<Response<Body>, Infallible>> + Send + 'static,
+    {
+        if let Err(e) = router::parse_pattern(path) {
+            panic!("Invalid route path '{}': {}", path, e);
+        }
+
         self.routes.push(Route {
             method,
             path: path.to_string(),
-            handler,
+            handler: Arc::new(move |req, params| boxed(handler(req, params))),
+            auto_head: true,
+            auto_options: true,
+            name: None,
+            host: None,
+            middleware: Vec::new(),
+            timeout: None,
+            body_limit: None,
         });
+        self.routes.last_mut().unwrap()
+    }
+
+    /// Adds a new named route to the Fluxor server.
+    /// 
+    /// Named routes can be turned back into URLs with `url_for`, also available to
+    /// handlers as `Params::url_for`, so templates do not hardcode paths.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `name`: The name of the route (e.g. "user_edit").
+    /// * `method`: The HTTP method for the route (GET, POST, etc.).
+    /// * `path`: The path for the route (see `route`).
+    /// * `handler`: A function or closure that handles requests to that route.
+    /// 
+    /// # Returns
+    /// 
+    /// The registered route, for further configuration.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn edit_user(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("edit"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.route_named("user_edit", GET, "/users/<id>/edit", edit_user);
+    /// 
+    /// assert_eq!(app.url_for("user_edit", &[("id", "42")]).as_deref(), Some("/users/42/edit"));
+    /// ```
+    pub fn route_named<H, F>(&mut self, name: &str, method: fluxio::Method, path: &str, handler: H) -> &mut Route
+    where
+        H: Fn(Request<Body>, Params) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<Response<Body>, Infallible>> + Send + 'static,
+    {
+        let route = self.route(method, path, handler);
+        route.name = Some(name.to_string());
+        route
+    }
+
+    /// Generates the URL path of a named route, filling in and percent-encoding its parameters.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `name`: The name of the route.
+    /// * `params`: Values for the route's path parameters.
+    /// 
+    /// # Returns
+    /// 
+    /// The URL path, or `None` if no route has that name or a parameter is missing
+    /// or does not satisfy its constraint.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
+        let route = self.routes.iter().find(|route| route.name.as_deref() == Some(name))?;
+        router::build_url(&route.path, params)
+    }
+
+    /// Collects the paths of named routes; the first route registered with a name wins.
+    fn named_urls(&self) -> HashMap<String, String> {
+        let mut urls = HashMap::new();
+        for route in &self.routes {
+            if let Some(name) = &route.name {
+                urls.entry(name.clone()).or_insert_with(|| route.path.clone());
+            }
+        }
+        urls
+    }
+
+    /// Mounts the routes of another Fluxor application under a path prefix.
+    /// 
+    /// Every route of `app` is registered on this application with `prefix` prepended
+    /// to its path, restricted to the host set with `set_host` on `app`, if any, and
+    /// wrapped in the middleware added with `wrap` on `app`, which thus acts as group
+    /// middleware running before the routes' own middleware. Routes without a time limit
+    /// or body size limit get the ones set with `set_timeout` and `set_body_limit` on
+    /// `app`, if any.
+    /// If `app` has a custom 404 closure (see `set_custom_404`), it is used
+    /// for unmatched requests under `prefix`. The static directory, MIME types, timeout
+    /// response settings (`set_timeout_status`, `set_custom_timeout`) and custom 500
+    /// of `app` are not used.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `prefix`: The path prefix (e.g. "/api/v1").
+    /// * `app`: The application whose routes are mounted.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn login(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("login"))) })
+    /// }
+    /// 
+    /// let mut auth = Fluxor::new();
+    /// auth.route(POST, "/login", login);
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.mount("/api/v1/auth", auth); // Serves POST /api/v1/auth/login
+    /// ```
+    pub fn mount(&mut self, prefix: &str, app: Fluxor) {
+        let prefix = prefix.trim_end_matches('/');
+
+        for mut route in app.routes {
+            route.path = join_paths(prefix, &route.path);
+            if route.host.is_none() {
+                route.host = app.host.clone();
+            }
+            route.middleware.splice(0..0, app.middleware.iter().cloned()); // Group middleware runs first
+            if route.timeout.is_none() {
+                route.timeout = app.timeout;
+            }
+            if route.body_limit.is_none() {
+                route.body_limit = app.body_limit;
+            }
+            self.routes.push(route);
+        }
+
+        for (group_prefix, closure) in app.group_404_closures {
+            self.group_404_closures.push((join_paths(prefix, &group_prefix), closure));
+        }
+
+        if let Some(closure) = app.custom_404_closure {
+            self.group_404_closures.push((prefix.to_string(), closure));
+        }
+    }
+
+    /// Registers a group of routes sharing a path prefix.
+    /// 
+    /// The closure receives a fresh `Fluxor` to register the group's routes (and optionally
+    /// its own custom 404), which is then mounted under `prefix` (see `mount`).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `prefix`: The path prefix shared by the group's routes.
+    /// * `build`: A closure registering the group's routes.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn stats(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("stats"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.group("/admin", |admin| {
+    ///     admin.route(GET, "/stats", stats); // Serves GET /admin/stats
+    ///     admin.set_custom_404(|_| "No such admin page.".to_string());
+    /// });
+    /// ```
+    pub fn group<F>(&mut self, prefix: &str, build: F)
+    where
+        F: FnOnce(&mut Fluxor),
+    {
+        let mut group = Fluxor::new();
+        build(&mut group);
+        self.mount(prefix, group);
+    }
+
+    /// Adds a middleware running around every request.
+    /// 
+    /// Global middleware wraps route dispatch as well as static file serving and the
+    /// 400/404/405 and OPTIONS fallbacks, and runs in the order it was added. On an
+    /// application that is mounted (see `mount` and `group`), it only wraps that
+    /// application's routes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `middleware`: The middleware (see `Middleware`).
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn stats(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("stats"))) })
+    /// }
+    /// 
+    /// fn require_admin(req: Req, params: Params, next: Next) -> Reply {
+    ///     if req.headers().get("X-Role").is_some_and(|role| role == "admin") {
+    ///         return next.run(req, params);
+    ///     }
+    ///     boxed(async {
+    ///         Ok(Response::builder().status(StatusCode::FORBIDDEN).body(Body::empty()).unwrap())
+    ///     })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.group("/admin", |admin| {
+    ///     admin.wrap(require_admin); // Only guards the group's routes
+    ///     admin.route(GET, "/stats", stats);
+    /// });
+    /// ```
+    pub fn wrap<M: Middleware>(&mut self, middleware: M) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Restricts every route of this application without its own host pattern (see
+    /// `Route::host`) to requests whose `Host` matches `pattern`.
+    /// 
+    /// This is mostly useful on a sub-application before mounting it, to serve it on
+    /// its own hostname.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `pattern`: The host pattern (e.g. "api.example.com" or "<tenant>.example.com").
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `pattern` is not a valid host pattern.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn dashboard(_req: Req, params: Params) -> Reply {
+    ///     let body = format!("Dashboard of {}", params.extra["tenant"]);
+    ///     boxed(async move { Ok(Response::new(Body::from(body))) })
+    /// }
+    /// 
+    /// let mut tenants = Fluxor::new();
+    /// tenants.route(GET, "/", dashboard);
+    /// tenants.set_host("<tenant>.example.com");
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.mount("/", tenants);
+    /// ```
+    pub fn set_host(&mut self, pattern: &str) {
+        if let Err(e) = router::parse_host(pattern) {
+            panic!("Invalid host '{}': {}", pattern, e);
+        }
+        self.host = Some(pattern.to_string());
+    }
+
+    /// Enables request IDs, carried in the given header.
+    /// 
+    /// Each request keeps the ID it came with in that header, if it is valid (1 to 128
+    /// letters, digits or `-_.:+/=`), or gets a new one. The ID is available to middleware
+    /// and handlers with `request_id(&req)`, echoed on the response, and included in
+    /// access logs and default error pages.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `header`: The header name (e.g. "X-Request-Id").
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `header` is not a valid header name.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.set_request_id("X-Request-Id");
+    /// app.wrap(AccessLog::new(LogFormat::Json)); // Logs include "request_id"
+    /// ```
+    pub fn set_request_id(&mut self, header: &str) {
+        if HeaderName::from_bytes(header.as_bytes()).is_err() {
+            panic!("Invalid request ID header '{}'", header);
+        }
+        self.request_id_header = Some(header.to_string());
+    }
+
+    /// Sets how requests differing from a route only by a trailing slash are handled.
+    /// 
+    /// Duplicate slashes and dot segments (`/a//b`, `/a/./b`) are always normalized
+    /// before routing, whatever the policy.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `policy`: `TrailingSlash::Strict` (default), `TrailingSlash::Redirect` (308 to the
+    ///   canonical path) or `TrailingSlash::MatchBoth`.
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    /// Returns the route table: method, path pattern, name and host of every registered route.
+    /// 
+    /// Set the `SERVER_ROUTES` environment variable to "show" to also print the table
+    /// in the startup banner of `run`.
+    /// 
+    /// # Returns
+    /// 
+    /// One `RouteInfo` per route, in registration order.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn show(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("show"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.route_named("user_show", GET, "/users/<id>", show);
+    /// 
+    /// for info in app.route_table() {
+    ///     println!("{} {} {:?}", info.method, info.path, info.name);
+    /// }
+    /// ```
+    pub fn route_table(&self) -> Vec<RouteInfo> {
+        self.routes.iter().map(RouteInfo::from_route).collect()
+    }
+
+    /// Serves the route table as JSON at the given path, for development tooling.
+    /// 
+    /// The endpoint answers GET requests with an array of `{"method", "path", "name", "host", "middleware"}`
+    /// objects describing every route. It is only registered in debug builds; release
+    /// builds ignore this setting.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `path`: The path of the endpoint (e.g. "/_routes").
+    pub fn serve_route_table(&mut self, path: &str) {
+        self.route_table_path = Some(path.to_string());
+    }
+
+    /// Detects duplicate and shadowed routes, and route names used more than once.
+    /// 
+    /// A route is shadowed when an earlier route has the same method and matches the
+    /// same requests, e.g. `/a/<x>` and `/a/<y>`; it can never be reached. `run` reports
+    /// duplicate and shadowed routes as errors and reused names as warnings at startup
+    /// (see `set_strict_routes`).
+    /// 
+    /// # Returns
+    /// 
+    /// The conflicts found, in registration order.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust
+    /// use fluxor::prelude::*;
+    /// 
+    /// fn show(_req: Req, _params: Params) -> Reply {
+    ///     boxed(async { Ok(Response::new(Body::from("show"))) })
+    /// }
+    /// 
+    /// let mut app = Fluxor::new();
+    /// app.route(GET, "/a/<x>", show);
+    /// app.route(GET, "/a/<y>", show); // Never reached
+    /// 
+    /// let conflicts = app.check_routes();
+    /// assert_eq!(conflicts.len(), 1);
+    /// assert!(conflicts[0].is_error());
+    /// ```
+    pub fn check_routes(&self) -> Vec<RouteConflict> {
+        router::find_conflicts(&self.routes)
+    }
+
+    /// Sets whether `run` refuses to start when route conflicts are detected.
+    /// 
+    /// Conflicts are always reported as warnings or errors at startup; in strict
+    /// mode the server does not start if there are any.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `strict`: Whether route conflicts prevent the server from starting.
+    pub fn set_strict_routes(&mut self, strict: bool) {
+        self.strict_routes = strict;
     }
 
     /// Includes new MIME types in the server configuration.
@@ -159,33 +943,116 @@ impl Fluxor {
         }
     }
 
+    /// Compiles the routes and middleware into the pipeline serving requests.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `table`: The route table, served as JSON in debug builds (see `serve_route_table`).
+    /// 
+    /// # Returns
+    /// 
+    /// The global middleware chain around the router, with the server settings.
+    fn pipeline(&self, table: &[RouteInfo]) -> Arc<Pipeline> {
+        let mut params = self.params.clone();
+        params.urls = Arc::new(self.named_urls());
+
+        let mut routes = self.routes.clone();
+
+        for route in routes.iter_mut().filter(|route| route.host.is_none()) {
+            route.host = self.host.clone();
+        }
+
+        if cfg!(debug_assertions) && let Some(path) = &self.route_table_path {
+            let json = introspect::route_table_json(table);
+            let mut dev = Fluxor::new();
+            dev.route(fluxio::Method::GET, path, move |_req, _params| {
+                let json = json.clone();
+                async move {
+                    Ok(Response::builder()
+                        .header("Content-Type", "application/json")
+                        .body(Body::from(json))
+                        .unwrap())
+                }
+            });
+            routes.append(&mut dev.routes);
+        }
+
+        let app = Arc::new(App {
+            params,
+            router: Router::new(&routes), // Compile the routing tree once
+            handlers: routes
+                .iter()
+                .map(|route| Next::new(route.middleware.clone().into(), route.handler.clone()))
+                .collect(),
+            routes,
+            mime_types: self.mime_types.clone(),
+            custom_404_closure: self.custom_404_closure.clone(),
+            group_404_closures: self.group_404_closures.clone(),
+            custom_405_closure: self.custom_405_closure.clone(),
+            trailing_slash: self.trailing_slash,
+            timeout: self.timeout,
+            timeout_status: self.timeout_status,
+            custom_timeout_closure: self.custom_timeout_closure.clone(),
+            custom_500_closure: self.custom_500_closure.clone(),
+            body_limit: self.body_limit,
+        });
+
+        // Global middleware wraps the whole dispatch: routing, static files and fallbacks
+        let endpoint: Handler = {
+            let app = app.clone();
+            Arc::new(move |req, params| boxed(handle_request(req, params, app.clone())))
+        };
+        Arc::new(Pipeline {
+            chain: Next::new(self.middleware.clone().into(), endpoint),
+            params: app.params.clone(),
+            request_id_header: self.request_id_header.as_ref().map(|header| {
+                HeaderName::from_bytes(header.as_bytes()).expect("validated by set_request_id")
+            }),
+            custom_500_closure: self.custom_500_closure.clone(),
+        })
+    }
+
     /// Starts the HTTP server on the provided host and port, processing incoming requests.
     /// 
+    /// Route conflicts (see `check_routes`) are reported first; in strict mode
+    /// (see `set_strict_routes`) the server does not start if there are any.
+    /// 
     /// # Arguments
     /// 
     /// * `host`: The host IP address (e.g., "127.0.0.1").
     /// * `port`: The port number (e.g., "8080").
     pub async fn run(&self, host: &str, port: &str) {
-        let make_svc = make_service_fn(|_conn| {
-            let params = self.params.clone();
-            let routes = self.routes.clone();
-            let mime_types = self.mime_types.clone();
-            let custom_404_closure = self.custom_404_closure.clone();
+        let conflicts = self.check_routes();
+        for conflict in &conflicts {
+            if conflict.is_error() || self.strict_routes {
+                println!("{} {}", "[ERROR]".red().bold(), conflict);
+            } else {
+                println!("{} {}", "[WARN]".yellow().bold(), conflict);
+            }
+        }
+        if self.strict_routes && !conflicts.is_empty() {
+            println!(
+                "{} ❌ Server not started: {} route conflict(s) in strict mode.",
+                "[ERROR]".red().bold(), conflicts.len()
+            );
+            return;
+        }
+
+        let table = self.route_table();
+        let pipeline = self.pipeline(&table);
+
+        let make_svc = make_service_fn(move |conn: &AddrStream| {
+            let pipeline = pipeline.clone();
+            let remote_addr = RemoteAddr(conn.remote_addr());
 
             async move {
-                Ok::<_, Infallible>(service_fn(move |req| {
-                    let params = params.clone();
-                    let routes = routes.clone();
-                    handle_request(req, params.clone(), routes.clone(), mime_types.clone(), custom_404_closure.clone())
-                }))
+                Ok::<_, Infallible>(service_fn(move |req| serve(req, remote_addr, pipeline.clone())))
             }
         });
 
         let addr = format!("{}:{}", host, port);
         let addr: SocketAddr = addr.parse().expect("Invalid address/port combination");
 
-        Server::bind(&addr).serve(make_svc);
-
         let server = Server::bind(&addr).serve(make_svc);
 
         // Get the current local formated timestamp
@@ -206,6 +1073,11 @@ impl Fluxor {
             project_name.bright_green().bold(), "has started.".bright_green().bold(), "[INFO]".cyan(), "on".magenta(), address.underline(), "[TIME]".cyan(), day_name, timestamp.blue(), "[SHUTDOWN]".cyan(),
         );
 
+        let startup_message = match env_var("SERVER_ROUTES", "hide").as_str() {
+            "show" => format!("{}\n{}\n{}", startup_message, "[ROUTES]".cyan(), introspect::route_table_text(&table)),
+            _ => startup_message,
+        };
+
         let server_view = env_var("SERVER_VIEW", "show");
 
         server_log(&server_view, &startup_message); // Log the server status
@@ -223,15 +1095,15 @@ impl Fluxor {
 /// 
 /// # Arguments
 /// 
-/// * `req`: The incoming request.
+/// * `path`: The normalized request path (see `router::normalize_path`).
 /// * `params`: Parameters containing directory information.
 /// * `mime_types`: A HashMap of MIME types to use for the response.
 /// 
 /// # Returns
 /// 
 /// A Result containing a Response with the file content or a 404 error.
-async fn serve_static_file(req:  &Req, params: &Params, mime_types: &HashMap<String, String>) -> Result<Response<Body>, Infallible> {
-    let path = params.dir.join(req.uri().path().trim_start_matches('/'));
+async fn serve_static_file(path: &str, params: &Params, mime_types: &HashMap<String, String>) -> Result<Response<Body>, Infallible> {
+    let path = params.dir.join(path.trim_start_matches('/'));
     match async_fs::read(&path).await {
         Ok(content) => {
             let extension = path
@@ -263,7 +1135,7 @@ async fn serve_static_file(req:  &Req, params: &Params, mime_types: &HashMap<Str
 /// 
 /// * `req` - A reference to the incoming HTTP request, used to inspect headers for content negotiation.
 /// * `_params` - A reference to the server parameters, which may include configuration details.
-/// * `custom_closure` - An `Option` containing an `Arc`-wrapped closure that takes a `&str` (content type)
+/// * `custom_closure` - An `Option` referencing an `Arc`-wrapped closure that takes a `&str` (content type)
 ///   and returns a `String`. This closure, if provided, dynamically generates the response body.
 ///
 /// # Returns
@@ -310,7 +1182,142 @@ async fn serve_static_file(req:  &Req, params: &Params, mime_types: &HashMap<Str
 ///     });
 /// }
 /// ```
-fn not_found_response(req: &Req, _params: &Params, custom_closure: &Option<Arc<dyn Fn(&str) -> String + Send + Sync>>) -> Response<Body> {
+fn not_found_response(req: &Req, _params: &Params, custom_closure: Option<&ResponseClosure>) -> Response<Body> {
+    error_response(req, StatusCode::NOT_FOUND, custom_closure)
+}
+
+/// Generates a 405 Method Not Allowed response with an `Allow` header.
+///
+/// The body is negotiated like the 404 response (see `not_found_response`) and may be
+/// customized with `Fluxor::set_custom_405`.
+///
+/// # Arguments
+/// 
+/// * `req` - A reference to the incoming HTTP request, used for content negotiation.
+/// * `allowed` - The methods registered for the request path.
+/// * `custom_closure` - An optional closure generating the response body for a content type.
+///
+/// # Returns
+/// 
+/// A `Response<Body>` with status code 405 and an `Allow` header listing `allowed`.
+fn method_not_allowed_response(req: &Req, allowed: &[fluxio::Method], custom_closure: Option<&ResponseClosure>) -> Response<Body> {
+    let mut response = error_response(req, StatusCode::METHOD_NOT_ALLOWED, custom_closure);
+    response.headers_mut().insert("Allow", allow_header(allowed).parse().unwrap());
+    response
+}
+
+/// Generates the automatic answer to an OPTIONS request.
+///
+/// # Arguments
+/// 
+/// * `allowed` - The methods registered for the request path.
+///
+/// # Returns
+/// 
+/// A `204 No Content` response with an `Allow` header listing `allowed`.
+fn options_response(allowed: &[fluxio::Method]) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("Allow", allow_header(allowed))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Generates a `308 Permanent Redirect` to the canonical form of the request path,
+/// keeping the query string.
+fn redirect_response(req: &Req, path: &str) -> Response<Body> {
+    let location = match req.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    Response::builder()
+        .status(StatusCode::PERMANENT_REDIRECT)
+        .header("Location", location)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Formats methods as the value of an `Allow` header (e.g. "GET, HEAD, OPTIONS").
+fn allow_header(allowed: &[fluxio::Method]) -> String {
+    allowed.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Returns the body size declared by a request's `Content-Length` header, if any.
+fn declared_length(req: &Req) -> Option<u64> {
+    req.headers()
+        .get("Content-Length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Limits the size of a request body of unknown length, as it streams in.
+/// 
+/// The body is replaced with one fed by a task forwarding chunks until `limit` bytes
+/// are exceeded, at which point the new body fails with an error.
+/// 
+/// # Arguments
+/// 
+/// * `req`: The request.
+/// * `limit`: The maximum body size in bytes.
+/// 
+/// # Returns
+/// 
+/// A flag set when the limit is exceeded, or `None` if the body length is already
+/// enforced (declared with `Content-Length`, or empty).
+fn limit_body(req: &mut Req, limit: u64) -> Option<Arc<AtomicBool>> {
+    if declared_length(req).is_some() || req.body().is_end_stream() {
+        return None;
+    }
+
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let flag = exceeded.clone();
+    let (mut sender, body) = Body::channel();
+    let mut original = std::mem::replace(req.body_mut(), body);
+
+    tokio::spawn(async move {
+        let mut total = 0u64;
+        while let Some(chunk) = original.data().await {
+            let Ok(chunk) = chunk else {
+                return sender.abort();
+            };
+            total += chunk.len() as u64;
+            if total > limit {
+                flag.store(true, Ordering::SeqCst);
+                return sender.abort();
+            }
+            if sender.send_data(chunk).await.is_err() {
+                return; // The handler dropped the body
+            }
+        }
+        if let Ok(Some(trailers)) = original.trailers().await {
+            let _ = sender.send_trailers(trailers).await;
+        }
+    });
+
+    Some(exceeded)
+}
+
+/// Drops the body of a GET response served for a HEAD request, keeping its `Content-Length`.
+async fn strip_body(response: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+
+    if !parts.headers.contains_key("Content-Length") {
+        let length = match HttpBody::size_hint(&body).exact() {
+            Some(length) => Some(length),
+            None => to_bytes(body).await.ok().map(|bytes| bytes.len() as u64),
+        };
+        if let Some(length) = length {
+            parts.headers.insert("Content-Length", length.into());
+        }
+    }
+
+    Response::from_parts(parts, Body::empty())
+}
+
+/// Picks the response content type for error pages from the `Accept` header of the request.
+///
+/// Supports `application/json`, `text/html`, and plain text as fallback.
+fn preferred_content_type(req: &Req) -> &'static str {
     // Get the Accept header
     let accept_header = req.headers()
         .get("Accept")
@@ -324,84 +1331,294 @@ fn not_found_response(req: &Req, _params: &Params, custom_closure: &Option<Arc<d
     .collect();
 
     // Determine the content type based on media types
-    let content_type = if media_types.iter().any(|mt| *mt == "application/json" || *mt == "application/*" || *mt == "*/*") {
+    if media_types.iter().any(|mt| *mt == "application/json" || *mt == "application/*" || *mt == "*/*") {
         "application/json"
     } else if media_types.iter().any(|mt| *mt == "text/html" || *mt == "text/*" || *mt == "*/*") {
         "text/html"
     } else {
         "text/plain"
-    };
+    }
+}
 
+/// Builds an error response for a status code, using a custom closure for the body if set.
+///
+/// The default bodies include the request ID, if request IDs are enabled.
+///
+/// # Arguments
+/// 
+/// * `req` - The request, used for content negotiation (see `preferred_content_type`).
+/// * `status` - The status code of the response.
+/// * `custom_closure` - An optional closure generating the response body for a content type.
+///
+/// # Returns
+/// 
+/// A `Response<Body>` with the given status, `Content-Type` and either the custom or default content.
+fn error_response(req: &Req, status: StatusCode, custom_closure: Option<&ResponseClosure>) -> Response<Body> {
+    error_page(status, preferred_content_type(req), request_id(req), custom_closure)
+}
+
+/// Builds an error response for a negotiated content type (see `error_response`).
+///
+/// # Arguments
+/// 
+/// * `status` - The status code of the response.
+/// * `content_type` - The negotiated content type (see `preferred_content_type`).
+/// * `request_id` - The request ID, if enabled.
+/// * `custom_closure` - An optional closure generating the response body for a content type.
+///
+/// # Returns
+/// 
+/// A `Response<Body>` with the given status, `Content-Type` and either the custom or default content.
+fn error_page(status: StatusCode, content_type: &str, request_id: Option<&str>, custom_closure: Option<&ResponseClosure>) -> Response<Body> {
     // Use custom closure if set
-    if let Some(closure) = custom_closure {
-        let content = closure(content_type);
-        Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .header("Content-Type", content_type)
-            .body(Body::from(content))
-            .unwrap()
+    let content = if let Some(closure) = custom_closure {
+        closure(content_type)
     } else {
         // fallback default content
-        let default_content = match content_type {
-            "application/json" => r#"{"error": {"code": 404, "message": "Not Found"}}"#,
-            "text/html" => "<html><body><h1>404 - Not Found</h1></body></html>",
-            _ => "404 Resource Not Found",
-        };
-        Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .header("Content-Type", content_type)
-            .body(Body::from(default_content))
-            .unwrap()
+        let code = status.as_u16();
+        let reason = status.canonical_reason().unwrap_or("Error");
+        match (content_type, request_id) {
+            ("application/json", None) => format!(r#"{{"error": {{"code": {}, "message": "{}"}}}}"#, code, reason),
+            ("application/json", Some(id)) => format!(r#"{{"error": {{"code": {}, "message": "{}", "request_id": "{}"}}}}"#, code, reason, id),
+            ("text/html", None) => format!("<html><body><h1>{} - {}</h1></body></html>", code, reason),
+            ("text/html", Some(id)) => format!("<html><body><h1>{} - {}</h1><p>Request ID: {}</p></body></html>", code, reason, id),
+            (_, id) => {
+                let text = match status {
+                    StatusCode::NOT_FOUND => "404 Resource Not Found".to_string(),
+                    _ => format!("{} {}", code, reason),
+                };
+                match id {
+                    Some(id) => format!("{}\nRequest ID: {}", text, id),
+                    None => text,
+                }
+            }
+        }
+    };
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .body(Body::from(content))
+        .unwrap()
+}
+
+/// Serves a request: records the connection details and request ID in the request
+/// extensions, then runs the global middleware chain.
+/// 
+/// # Arguments
+/// 
+/// * `req`: The incoming request.
+/// * `remote_addr`: The peer address of the connection.
+/// * `pipeline`: The global middleware chain and server settings.
+/// 
+/// # Returns
+/// 
+/// A Result containing the Response, with the request ID header if enabled.
+async fn serve(mut req: Req, remote_addr: RemoteAddr, pipeline: Arc<Pipeline>) -> Result<Response<Body>, Infallible> {
+    req.extensions_mut().insert(remote_addr);
+    let echoed_id = pipeline
+        .request_id_header
+        .as_ref()
+        .map(|header| (header.clone(), request_id::assign(&mut req, header)));
+
+    // Last resort for panics in global middleware; route panics are caught in `handle_request`
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let content_type = preferred_content_type(&req);
+    let id = request_id(&req).map(str::to_string);
+    let future = recover::catch_unwind(|| pipeline.chain.clone().run(req, pipeline.params.clone()));
+
+    let mut response = match future.await {
+        Ok(response) => response,
+        Err(message) => {
+            log_panic(&method, &path, id.as_deref(), &message);
+            error_page(StatusCode::INTERNAL_SERVER_ERROR, content_type, id.as_deref(), pipeline.custom_500_closure.as_ref())
+        }
+    };
+
+    if let Some((header, id)) = echoed_id {
+        response.headers_mut().insert(header, id);
     }
+    Ok(response)
 }
 
 /// Unified request handler that processes incoming requests against defined routes.
 /// 
+/// This is the endpoint of the global middleware chain; matched routes run through
+/// their own middleware chain.
+/// 
 /// # Arguments
 /// 
 /// * `req`: The incoming request.
-/// * `params`: Parameters for the request context.
-/// * `routes`: Vector of available routes.
-/// * `mime_types`: HashMap of MIME types to be used for static file serving.
+/// * `params`: The server parameters, as passed down by the global middleware.
+/// * `app`: The compiled server state holding the router, routes and MIME types.
 /// 
 /// # Returns
 /// 
-/// A Result containing a Response for the incoming request, which may include a static file or a 404 error.
-async fn handle_request(
-    req: Req,
-    params: Params,
-    routes: Vec<Route>,
-    mime_types: HashMap<String, String>, // Add MIME types to parameters
-    custom_closure: Option<Arc<dyn Fn(&str) -> String + Send + Sync>>,
-) -> Result<Response<Body>, Infallible> {
-    for route in routes.iter() {
-        if route.method == *req.method() {
-            if let Some(captured_params) = route.is_match(&req) {
-                let mut new_params = params.clone();
-                new_params.extra.extend(captured_params); // Add captured params to the existing ones
-
-                // Check if there's a '/' at the end of the URL without a subsequent value
-                if new_params.extra.values().any(|value| value.is_empty()) {
-                    return Ok(Response::builder()
-                        .status(StatusCode::NOT_FOUND)
-                        .body(Body::from("Resource not found"))
-                        .unwrap());
-                }
+/// A Result containing a Response for the incoming request, which may include a static file,
+/// a 400 error when a captured path parameter is not valid percent-encoded UTF-8, a 405 error
+/// when the path only matches routes for other methods, or a 404 error.
+async fn handle_request(mut req: Req, params: Params, app: Arc<App>) -> Result<Response<Body>, Infallible> {
+    let host = request_host(&req);
+    let mut path = router::normalize_path(req.uri().path());
+    let mut lookup = app.router.lookup(req.method(), host.as_deref(), &path);
+
+    // Try the path with the trailing slash toggled, unless the policy is strict
+    if matches!(lookup, Lookup::NotFound)
+        && app.trailing_slash != TrailingSlash::Strict
+        && let Some(alternate) = router::toggle_trailing_slash(&path)
+    {
+        let alternate_lookup = app.router.lookup(req.method(), host.as_deref(), &alternate);
+        if !matches!(alternate_lookup, Lookup::NotFound) {
+            lookup = alternate_lookup;
+            path = alternate;
+        }
+    }
 
-                let future = (route.handler)(req, new_params);
-                return future.await;
+    if app.trailing_slash == TrailingSlash::Redirect
+        && path != req.uri().path()
+        && !matches!(lookup, Lookup::NotFound)
+    {
+        return Ok(redirect_response(&req, &path));
+    }
+
+    let captured_params = match lookup {
+        Lookup::Found { route, params, raw } => Some((route, params, raw)),
+        Lookup::BadRequest => {
+            return Ok(error_response(&req, StatusCode::BAD_REQUEST, None));
+        }
+        Lookup::Options(allowed) => {
+            return Ok(options_response(&allowed));
+        }
+        Lookup::MethodNotAllowed(allowed) => {
+            return Ok(method_not_allowed_response(&req, &allowed, app.custom_405_closure.as_ref()));
+        }
+        Lookup::NotFound => None,
+    };
+
+    if let Some((route, captured_params, raw_params)) = captured_params {
+        let mut new_params = params;
+        new_params.extra.extend(captured_params); // Add captured params to the existing ones
+        new_params.raw = raw_params;
+
+        let head = req.method() == fluxio::Method::HEAD && app.routes[route].method != fluxio::Method::HEAD;
+        let method = req.method().clone();
+        let content_type = preferred_content_type(&req);
+        let id = request_id(&req).map(str::to_string);
+
+        // Reject bodies declared too large up front, and count the others as they stream in
+        let exceeded = match app.routes[route].body_limit.or(app.body_limit) {
+            Some(limit) if declared_length(&req).is_some_and(|length| length > limit) => {
+                return Ok(error_response(&req, StatusCode::PAYLOAD_TOO_LARGE, None));
             }
+            Some(limit) => limit_body(&mut req, limit),
+            None => None,
+        };
+
+        let future = recover::catch_unwind(|| app.handlers[route].clone().run(req, new_params));
+
+        // Cancel the handler by dropping its future once the time limit expires
+        let response = match app.routes[route].timeout.or(app.timeout) {
+            Some(limit) => match tokio::time::timeout(limit, future).await {
+                Ok(response) => response,
+                Err(_) => {
+                    let closure = app.custom_timeout_closure.as_ref();
+                    return Ok(error_page(app.timeout_status, content_type, id.as_deref(), closure));
+                }
+            },
+            None => future.await,
+        };
+
+        if exceeded.is_some_and(|exceeded| exceeded.load(Ordering::SeqCst)) {
+            return Ok(error_page(StatusCode::PAYLOAD_TOO_LARGE, content_type, id.as_deref(), None));
         }
+
+        // A panicking handler gets a 500 instead of dropping the connection
+        let response = match response {
+            Ok(response) => response,
+            Err(message) => {
+                log_panic(&method, &path, id.as_deref(), &message);
+                let closure = app.custom_500_closure.as_ref();
+                return Ok(error_page(StatusCode::INTERNAL_SERVER_ERROR, content_type, id.as_deref(), closure));
+            }
+        };
+
+        return if head { Ok(strip_body(response).await) } else { Ok(response) };
     }
 
     // If no route matches, serve static files or return 404
-    let static_file_response = serve_static_file(&req, &params, &mime_types).await?;
+    let static_file_response = serve_static_file(&path, &params, &app.mime_types).await?;
     
     if static_file_response.status() == StatusCode::OK {
         Ok(static_file_response)
     } else {
         // Pass the params struct here!
-         Ok(not_found_response(&req, &params, &custom_closure))
+         Ok(not_found_response(&req, &params, app.custom_404_for(&path)))
+    }
+}
+
+/// Logs a panic raised while handling a request.
+/// 
+/// # Arguments
+/// 
+/// * `method`: The request method.
+/// * `path`: The request path.
+/// * `request_id`: The request ID, if enabled.
+/// * `message`: The panic message.
+fn log_panic(method: &fluxio::Method, path: &str, request_id: Option<&str>, message: &str) {
+    let request_id = request_id.map_or(String::new(), |id| format!(" (request ID: {})", id));
+    println!(
+        "{} ❌ Panic while handling {} {}{}: '{}'",
+        "[ERROR]".red().bold(), method, path, request_id, message
+    );
+}
+
+/// Returns the address of the client that sent a request.
+/// 
+/// # Arguments
+/// 
+/// * `req`: The request.
+/// 
+/// # Returns
+/// 
+/// The peer address of the connection, or `None` if the request did not come
+/// through `Fluxor::run` (e.g. in tests).
+pub fn remote_addr(req: &Req) -> Option<SocketAddr> {
+    req.extensions().get::<RemoteAddr>().map(|addr| addr.0)
+}
+
+/// Returns the host of a request, from the `Host` header or the URI authority,
+/// lowercased and without the port.
+fn request_host(req: &Req) -> Option<String> {
+    let host = req.headers()
+        .get("Host")
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| req.uri().host())?;
+
+    // Strip the port, leaving IPv6 literals such as "[::1]" intact
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit())
+            && (name.ends_with(']') || !name.contains(':')) => name,
+        _ => host,
+    };
+
+    Some(host.trim_end_matches('.').to_ascii_lowercase())
+}
+
+/// Joins a mount prefix and a route path, mapping the root path to the prefix itself.
+fn join_paths(prefix: &str, path: &str) -> String {
+    match path {
+        "" | "/" if !prefix.is_empty() => prefix.to_string(),
+        _ if path.starts_with('/') => format!("{}{}", prefix, path),
+        _ => format!("{}/{}", prefix, path),
+    }
+}
+
+/// Checks whether a request path lies under a mount prefix (on a segment boundary).
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'),
+        None => false,
     }
 }
 
@@ -513,3 +1730,152 @@ pub fn serve_http_client(_req: Req, _params: Params) -> Reply {
             .unwrap())
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluxio::Method;
+
+    /// Sends a request through the compiled pipeline of an application, as `run` does.
+    async fn send(app: &Fluxor, req: Req) -> Response<Body> {
+        let remote_addr = RemoteAddr(SocketAddr::from(([127, 0, 0, 1], 8080)));
+        match serve(req, remote_addr, app.pipeline(&[])).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    }
+
+    async fn body_text(response: Response<Body>) -> String {
+        String::from_utf8(to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap()
+    }
+
+    fn get(path: &str) -> Req {
+        Request::get(path).body(Body::empty()).unwrap()
+    }
+
+    fn timeout_app() -> Fluxor {
+        let mut app = Fluxor::new();
+        app.set_timeout(Duration::from_millis(50));
+        app.set_custom_timeout(|content_type| format!("timed out ({})", content_type));
+        app.route(Method::GET, "/slow", |_req, _params| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(Response::new(Body::from("late")))
+        });
+        app.route(Method::GET, "/fast", |_req, _params| async {
+            Ok(Response::new(Body::from("fast")))
+        });
+        app
+    }
+
+    #[tokio::test]
+    async fn timed_out_handlers_get_the_custom_timeout_response() {
+        let mut app = timeout_app();
+
+        let response = send(&app, get("/slow")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_text(response).await, "timed out (text/plain)");
+
+        let response = send(&app, get("/fast")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "fast");
+
+        app.set_timeout_status(StatusCode::GATEWAY_TIMEOUT);
+        let response = send(&app, get("/slow")).await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body_text(response).await, "timed out (text/plain)");
+    }
+
+    #[tokio::test]
+    async fn route_timeouts_override_the_default() {
+        let mut app = timeout_app();
+        app.route(Method::GET, "/report", |_req, _params| async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok(Response::new(Body::from("report")))
+        })
+        .timeout(Duration::from_secs(10));
+
+        let response = send(&app, get("/report")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "report");
+    }
+
+    fn panics_early(_req: Req, _params: Params) -> Reply {
+        panic!("panicked while creating the future")
+    }
+
+    fn panics_late(_req: Req, _params: Params) -> Reply {
+        boxed(async { panic!("panicked while polling the future") })
+    }
+
+    #[tokio::test]
+    async fn panicking_handlers_get_the_custom_500_response() {
+        let mut app = Fluxor::new();
+        app.set_custom_500(|content_type| format!("crashed ({})", content_type));
+        app.route(Method::GET, "/early", panics_early);
+        app.route(Method::GET, "/late", panics_late);
+
+        for path in ["/early", "/late"] {
+            let response = send(&app, get(path)).await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(body_text(response).await, "crashed (text/plain)");
+        }
+    }
+
+    fn upload_app() -> Fluxor {
+        let mut app = Fluxor::new();
+        app.set_body_limit(16);
+        app.route(Method::POST, "/upload", |req: Req, _params| async move {
+            Ok(match to_bytes(req.into_body()).await {
+                Ok(body) => Response::new(Body::from(body)),
+                Err(_) => Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from("read failed"))
+                    .unwrap(),
+            })
+        });
+        app
+    }
+
+    /// Builds a POST request whose body streams in the given chunks, without a length.
+    fn streamed(chunks: &[&'static str]) -> Req {
+        let (mut sender, body) = Body::channel();
+        let chunks = chunks.to_vec();
+        tokio::spawn(async move {
+            for chunk in chunks {
+                if sender.send_data(chunk.into()).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Request::post("/upload").body(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn declared_bodies_over_the_limit_are_rejected() {
+        let app = upload_app();
+        let req = Request::post("/upload")
+            .header("Content-Length", "17")
+            .body(Body::from("x".repeat(17)))
+            .unwrap();
+
+        let response = send(&app, req).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn streamed_bodies_over_the_limit_are_cut_off() {
+        let app = upload_app();
+
+        let response = send(&app, streamed(&["0123456789", "0123456789", "0123456789"])).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn streamed_bodies_under_the_limit_pass_intact() {
+        let app = upload_app();
+
+        let response = send(&app, streamed(&["01234", "56789", "abcdef"])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_text(response).await, "0123456789abcdef");
+    }
+}
@@ -0,0 +1,328 @@
+use super::{Middleware, Next, Params, Reply, Req, boxed, error_response, remote_addr};
+use fluxio::StatusCode;
+use fluxio::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The future returned by `RateLimitStore::hit`.
+pub type StoreFuture = Pin<Box<dyn Future<Output = RateLimitDecision> + Send>>;
+
+/// Returns the client key of a request for a rate limiter (see `RateLimit::key_by`).
+pub type KeyClosure = Arc<dyn Fn(&Req) -> Option<String> + Send + Sync>;
+
+/// Checks a header value before it is used as a client key (see `RateLimit::key_by_header`).
+pub type KeyValidator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// The quota of a rate limiter: a token bucket holding up to `burst` tokens, refilled
+/// with `limit` tokens per `window`. Each request takes one token.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    pub limit: u32,       // Tokens added per window
+    pub window: Duration, // Length of the window
+    pub burst: u32,       // Capacity of the bucket
+}
+
+/// The outcome of taking a token from a bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,         // Whether the request may proceed
+    pub remaining: u32,        // Tokens left in the bucket
+    pub reset: Duration,       // Time until the bucket is full again
+    pub retry_after: Duration, // Time until the next token, when the request is denied
+}
+
+/// Storage of the token buckets of a rate limiter.
+///
+/// The default `MemoryStore` keeps buckets in the process; implement this trait to share
+/// limits between instances (e.g. in Redis). Keys are only unique per limiter, so give
+/// each limiter sharing a store distinct keys.
+pub trait RateLimitStore: Send + Sync + 'static {
+    /// Takes a token from the bucket of a key, creating a full bucket if it does not exist.
+    fn hit(&self, key: String, quota: Quota) -> StoreFuture;
+}
+
+/// A bucket of the in-memory store.
+struct Bucket {
+    tokens: f64,      // Tokens left, refilled lazily
+    updated: Instant, // Time of the last refill
+}
+
+/// In-memory token bucket store, the default of `RateLimit`.
+///
+/// Idle buckets are removed periodically, once they would be full again.
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>, // Buckets by key
+    hits: AtomicU64,                         // Hits since startup, to schedule cleanups
+}
+
+impl MemoryStore {
+    /// Creates an empty in-memory store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    fn hit(&self, key: String, quota: Quota) -> StoreFuture {
+        let now = Instant::now();
+        let rate = quota.limit as f64 / quota.window.as_secs_f64().max(f64::EPSILON); // Tokens per second
+        let capacity = quota.burst as f64;
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if self.hits.fetch_add(1, Ordering::Relaxed) % 1024 == 1023 {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let decision = RateLimitDecision {
+            allowed,
+            remaining: bucket.tokens as u32,
+            reset: Duration::from_secs_f64((capacity - bucket.tokens) / rate),
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / rate),
+        };
+        Box::pin(async move { decision })
+    }
+}
+
+/// How a rate limiter identifies clients.
+#[derive(Clone)]
+pub enum RateLimitKey {
+    Ip,                           // Client IP address
+    Header(String, KeyValidator), // A validated header such as an API key, or the IP otherwise
+    Custom(KeyClosure),           // A closure; `None` exempts the request
+}
+
+/// Per-client rate limiting middleware, based on token buckets.
+///
+/// Requests over the quota get `429 Too Many Requests` with a `Retry-After` header.
+/// Every limited response carries `RateLimit-Limit` (the burst size), `RateLimit-Remaining`
+/// and `RateLimit-Reset` headers. Add it with `Fluxor::wrap` to limit every request, or with
+/// `Route::wrap` to limit a route on its own.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+/// use std::collections::HashSet;
+/// use std::time::Duration;
+///
+/// fn compute(_req: Req, _params: Params) -> Reply {
+///     boxed(async { Ok(Response::new(Body::from("42"))) })
+/// }
+///
+/// let mut app = Fluxor::new();
+///
+/// // 600 requests per minute per client IP, for every route
+/// app.wrap(RateLimit::new(600, Duration::from_secs(60)));
+///
+/// // 10 requests per second per known API key, with bursts of up to 20
+/// let api_keys: HashSet<String> = HashSet::from(["key-1".to_string(), "key-2".to_string()]);
+/// app.route(POST, "/compute", compute).wrap(
+///     RateLimit::new(10, Duration::from_secs(1))
+///         .burst(20)
+///         .key_by_header("X-Api-Key", move |key| api_keys.contains(key)),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct RateLimit {
+    quota: Quota,                   // Limit, window and burst
+    key: RateLimitKey,              // How clients are identified
+    store: Arc<dyn RateLimitStore>, // Storage of the buckets
+}
+
+impl RateLimit {
+    /// Creates a rate limiter allowing `limit` requests per `window` for each client IP,
+    /// with buckets kept in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit`: The number of requests allowed per window (also the burst size).
+    /// * `window`: The length of the window.
+    ///
+    /// # Returns
+    ///
+    /// A new RateLimit instance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0 or `window` is zero.
+    pub fn new(limit: u32, window: Duration) -> Self {
+        assert!(
+            limit > 0 && !window.is_zero(),
+            "Rate limit must allow at least one request per window"
+        );
+        Self {
+            quota: Quota {
+                limit,
+                window,
+                burst: limit,
+            },
+            key: RateLimitKey::Ip,
+            store: Arc::new(MemoryStore::new()),
+        }
+    }
+
+    /// Sets the number of requests a client can make at once (the bucket capacity).
+    ///
+    /// # Arguments
+    ///
+    /// * `burst`: The bucket capacity (at least 1).
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.quota.burst = burst.max(1);
+        self
+    }
+
+    /// Identifies clients by a request header, such as an API key.
+    ///
+    /// The header is only used as the key when `validate` accepts its value; requests
+    /// without the header, or with a value it rejects, are identified by their IP address.
+    /// Without this check a client could send a new value with every request and never be
+    /// limited. To key by a value checked elsewhere (e.g. an authenticated user), use
+    /// `key_by` or add the limiter after the authentication middleware.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The header name (e.g. "X-Api-Key").
+    /// * `validate`: A closure returning whether a header value is a known key.
+    pub fn key_by_header<F>(mut self, name: &str, validate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.key = RateLimitKey::Header(name.to_string(), Arc::new(validate));
+        self
+    }
+
+    /// Identifies clients with a closure. Requests for which it returns `None` are not limited.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: A closure returning the client key of a request.
+    pub fn key_by<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Req) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key = RateLimitKey::Custom(Arc::new(key));
+        self
+    }
+
+    /// Sets the store of the buckets (a `MemoryStore` by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `store`: The store.
+    pub fn store<S: RateLimitStore>(mut self, store: S) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    /// Returns the client key of a request, or `None` if it is not limited.
+    fn key_of(&self, req: &Req) -> Option<String> {
+        let ip = || remote_addr(req).map_or("-".to_string(), |addr| addr.ip().to_string());
+        match &self.key {
+            RateLimitKey::Ip => Some(format!("ip:{}", ip())),
+            RateLimitKey::Header(name, validate) => Some(
+                match req
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .filter(|value| validate(value))
+                {
+                    Some(value) => format!("header:{}", value),
+                    None => format!("ip:{}", ip()),
+                },
+            ),
+            RateLimitKey::Custom(key) => key(req),
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, req: Req, params: Params, next: Next) -> Reply {
+        let Some(key) = self.key_of(&req) else {
+            return next.run(req, params);
+        };
+        let hit = self.store.hit(key, self.quota);
+        let limit = self.quota.burst; // `Remaining` counts tokens out of the bucket capacity
+
+        boxed(async move {
+            let decision = hit.await;
+            if !decision.allowed {
+                let mut response = error_response(&req, StatusCode::TOO_MANY_REQUESTS, None);
+                let headers = response.headers_mut();
+                headers.insert(
+                    RETRY_AFTER,
+                    HeaderValue::from(ceil_secs(decision.retry_after)),
+                );
+                add_rate_limit_headers(headers, limit, &decision);
+                return Ok(response);
+            }
+
+            let mut response = next.run(req, params).await?;
+            add_rate_limit_headers(response.headers_mut(), limit, &decision);
+            Ok(response)
+        })
+    }
+}
+
+/// Adds the `RateLimit-*` headers describing a decision, `limit` being the bucket capacity.
+fn add_rate_limit_headers(headers: &mut HeaderMap, limit: u32, decision: &RateLimitDecision) {
+    headers.insert("RateLimit-Limit", HeaderValue::from(limit));
+    headers.insert("RateLimit-Remaining", HeaderValue::from(decision.remaining));
+    headers.insert(
+        "RateLimit-Reset",
+        HeaderValue::from(ceil_secs(decision.reset)),
+    );
+}
+
+/// Rounds a duration up to whole seconds.
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluxio::{Body, Request};
+
+    fn request(api_key: Option<&str>) -> Req {
+        let mut builder = Request::get("/");
+        if let Some(key) = api_key {
+            builder = builder.header("X-Api-Key", key);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn header_keys_are_used_only_when_valid() {
+        let limiter = RateLimit::new(10, Duration::from_secs(1))
+            .key_by_header("X-Api-Key", |key| key == "key-1");
+
+        assert_eq!(
+            limiter.key_of(&request(Some("key-1"))).as_deref(),
+            Some("header:key-1")
+        );
+        assert_eq!(
+            limiter.key_of(&request(Some("random"))).as_deref(),
+            Some("ip:-")
+        );
+        assert_eq!(limiter.key_of(&request(None)).as_deref(), Some("ip:-"));
+    }
+}
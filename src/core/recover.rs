@@ -0,0 +1,73 @@
+use super::Reply;
+use fluxio::{Body, Response};
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A future turning panics of a `Reply`, while it is created or polled, into errors.
+pub(crate) struct CatchUnwind {
+    future: Option<Reply>, // The guarded future, if it could be created
+    payload: Option<Box<dyn Any + Send>>, // Payload of a panic raised while creating it
+}
+
+/// Creates a future with `start` and guards it against panics.
+///
+/// # Arguments
+///
+/// * `start`: A closure creating the future, e.g. by calling a handler.
+///
+/// # Returns
+///
+/// A future resolving to the response, or to the panic message if `start` or the future panicked.
+pub(crate) fn catch_unwind<F>(start: F) -> CatchUnwind
+where
+    F: FnOnce() -> Reply,
+{
+    match panic::catch_unwind(AssertUnwindSafe(start)) {
+        Ok(future) => CatchUnwind {
+            future: Some(future),
+            payload: None,
+        },
+        Err(payload) => CatchUnwind {
+            future: None,
+            payload: Some(payload),
+        },
+    }
+}
+
+impl Future for CatchUnwind {
+    type Output = Result<Response<Body>, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(future) = self.future.as_mut() else {
+            let payload = self
+                .payload
+                .take()
+                .expect("CatchUnwind polled after completion");
+            return Poll::Ready(Err(panic_message(payload)));
+        };
+
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(Ok(response))) => Poll::Ready(Ok(response)),
+            Ok(Poll::Ready(Err(never))) => match never {},
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => {
+                self.future = None; // A panicked future must not be polled again
+                Poll::Ready(Err(panic_message(payload)))
+            }
+        }
+    }
+}
+
+/// Extracts the message of a panic payload.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Box<dyn Any>".to_string(),
+        },
+    }
+}
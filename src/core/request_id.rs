@@ -0,0 +1,86 @@
+use super::Req;
+use fluxio::header::{HeaderName, HeaderValue};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The ID of a request, stored in the request extensions when request IDs are enabled
+/// (see `Fluxor::set_request_id`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Returns the ID of a request.
+///
+/// # Arguments
+///
+/// * `req`: The request.
+///
+/// # Returns
+///
+/// The incoming or generated request ID, or `None` if request IDs are not enabled.
+///
+/// # Examples
+///
+/// ```rust
+/// use fluxor::prelude::*;
+///
+/// fn hello(req: Req, _params: Params) -> Reply {
+///     let body = format!("Request {}", request_id(&req).unwrap_or("-"));
+///     boxed(async move { Ok(Response::new(Body::from(body))) })
+/// }
+/// ```
+pub fn request_id(req: &Req) -> Option<&str> {
+    req.extensions().get::<RequestId>().map(|id| id.0.as_str())
+}
+
+/// Assigns an ID to a request: the one in `header` if it is valid, or a new one.
+///
+/// # Returns
+///
+/// The ID, to be echoed on the response.
+pub(crate) fn assign(req: &mut Req, header: &HeaderName) -> HeaderValue {
+    let id = match req
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(id) if is_valid(id) => id.to_string(),
+        _ => generate(),
+    };
+    let value = HeaderValue::from_str(&id).expect("request IDs are visible ASCII");
+    req.extensions_mut().insert(RequestId(id));
+    value
+}
+
+/// Checks whether an incoming ID can be trusted in logs and headers: 1 to 128 characters
+/// among letters, digits and `-_.:+/=`.
+fn is_valid(id: &str) -> bool {
+    (1..=128).contains(&id.len())
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.:+/=".contains(&byte))
+}
+
+/// Generates a request ID of 32 hexadecimal digits, unique within the process and
+/// unpredictable across restarts (not suitable as a secret).
+fn generate() -> String {
+    static STATE: OnceLock<RandomState> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let state = STATE.get_or_init(RandomState::new);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos());
+
+    let mut hasher = state.build_hasher();
+    hasher.write_u64(count);
+    hasher.write_u128(nanos);
+    let high = hasher.finish();
+    hasher.write_u64(high);
+    let low = hasher.finish();
+
+    format!("{:016x}{:016x}", high, low)
+}
@@ -0,0 +1,1067 @@
+use super::Route;
+use fluxio::Method;
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A constraint restricting which values a path parameter accepts.
+///
+/// Constraints are written after the parameter name, e.g. `<id:int>`, `<ratio:float>`,
+/// `<uuid:uuid>` or `<slug:re([a-z-]+)>`. A segment that does not satisfy the constraint
+/// does not match the route, so the request falls through to the next route or to 404.
+#[derive(Clone, Debug)]
+pub(crate) enum Constraint {
+    Any,          // No constraint, any segment is accepted
+    Int,          // A signed 64-bit integer
+    Float,        // A finite floating-point number
+    Uuid,         // A hyphenated UUID (8-4-4-4-12 hex digits)
+    Regex(Regex), // A regular expression that must match the whole segment
+}
+
+impl Constraint {
+    /// Parses the constraint part of a parameter (the text after `:`).
+    ///
+    /// # Arguments
+    ///
+    /// * `spec`: The constraint specification (e.g. `int` or `re([a-z]+)`).
+    ///
+    /// # Returns
+    ///
+    /// The parsed constraint, or an error message for unknown constraints and invalid regexes.
+    fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "int" => Ok(Constraint::Int),
+            "float" => Ok(Constraint::Float),
+            "uuid" => Ok(Constraint::Uuid),
+            _ => match spec.strip_prefix("re(").and_then(|s| s.strip_suffix(')')) {
+                Some(pattern) => Regex::new(&format!("^(?:{})$", pattern))
+                    .map(Constraint::Regex)
+                    .map_err(|e| format!("invalid regex '{}': {}", pattern, e)),
+                None => Err(format!("unknown constraint '{}'", spec)),
+            },
+        }
+    }
+
+    /// Checks whether a path segment satisfies the constraint.
+    pub(crate) fn matches(&self, value: &str) -> bool {
+        match self {
+            Constraint::Any => true,
+            Constraint::Int => value.parse::<i64>().is_ok(),
+            Constraint::Float => value.parse::<f64>().is_ok_and(f64::is_finite),
+            Constraint::Uuid => is_uuid(value),
+            Constraint::Regex(regex) => regex.is_match(value),
+        }
+    }
+
+    /// Returns a key identifying the constraint, used to share tree nodes between routes.
+    fn key(&self) -> &str {
+        match self {
+            Constraint::Any => "",
+            Constraint::Int => "int",
+            Constraint::Float => "float",
+            Constraint::Uuid => "uuid",
+            Constraint::Regex(regex) => regex.as_str(),
+        }
+    }
+}
+
+/// Checks for the hyphenated UUID form, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// A single segment of a route path pattern.
+#[derive(Clone, Debug)]
+pub(crate) enum Segment {
+    Static(String),            // Literal segment, e.g. `users`
+    Param(String, Constraint), // Captured segment, e.g. `<id>` or `<id:int>`
+    Wildcard(String),          // Captures the rest of the path, e.g. `<path..>`
+}
+
+impl Segment {
+    /// Parses one segment of a route path pattern.
+    fn parse(segment: &str) -> Result<Self, String> {
+        let Some(inner) = segment.strip_prefix('<').and_then(|s| s.strip_suffix('>')) else {
+            return Ok(Segment::Static(segment.to_string()));
+        };
+
+        if let Some(name) = inner.strip_suffix("..") {
+            if name.is_empty() {
+                return Err(format!("parameter '{}' has no name", segment));
+            }
+            return Ok(Segment::Wildcard(name.to_string()));
+        }
+
+        let (name, constraint) = match inner.split_once(':') {
+            Some((name, spec)) => (name, Constraint::parse(spec)?),
+            None => (inner, Constraint::Any),
+        };
+
+        if name.is_empty() {
+            return Err(format!("parameter '{}' has no name", segment));
+        }
+
+        Ok(Segment::Param(name.to_string(), constraint))
+    }
+}
+
+/// Splits a route path pattern into its segments.
+///
+/// Slashes inside a parameter (for example in a `re(...)` constraint) do not split the pattern.
+/// A wildcard segment such as `<path..>` is only allowed at the end of the pattern.
+///
+/// # Arguments
+///
+/// * `path`: The route path pattern (e.g. `/users/<id:int>`).
+///
+/// # Returns
+///
+/// A vector of parsed segments without the leading empty segment, or an error message
+/// describing the invalid part of the pattern.
+pub(crate) fn parse_pattern(path: &str) -> Result<Vec<Segment>, String> {
+    let segments = split_pattern(path.strip_prefix('/').unwrap_or(path), '/')?;
+
+    if let Some(position) = segments
+        .iter()
+        .position(|s| matches!(s, Segment::Wildcard(_)))
+        && position != segments.len() - 1
+    {
+        return Err("a wildcard parameter must be the last segment".to_string());
+    }
+
+    Ok(segments)
+}
+
+/// Splits a host pattern into its labels.
+///
+/// Labels are matched case-insensitively and may be parameters such as `<tenant>`
+/// (e.g. `<tenant>.example.com`); wildcards are not allowed.
+///
+/// # Arguments
+///
+/// * `host`: The host pattern, without a port.
+///
+/// # Returns
+///
+/// A vector of parsed labels, or an error message describing the invalid part of the pattern.
+pub(crate) fn parse_host(host: &str) -> Result<Vec<Segment>, String> {
+    let labels = split_pattern(host, '.')?;
+
+    if labels.iter().any(|s| matches!(s, Segment::Wildcard(_))) {
+        return Err("a host pattern cannot contain a wildcard parameter".to_string());
+    }
+
+    Ok(labels
+        .into_iter()
+        .map(|label| match label {
+            Segment::Static(literal) => Segment::Static(literal.to_ascii_lowercase()),
+            label => label,
+        })
+        .collect())
+}
+
+/// Splits a pattern on a separator that is not inside a `<...>` parameter.
+fn split_pattern(pattern: &str, separator: char) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut in_param = false;
+    let mut depth = 0; // Parenthesis depth inside a parameter
+
+    for (i, c) in pattern.char_indices() {
+        match c {
+            '<' if !in_param => in_param = true,
+            '(' if in_param => depth += 1,
+            ')' if in_param => depth -= 1,
+            '>' if in_param && depth == 0 => in_param = false,
+            c if c == separator && !in_param => {
+                segments.push(Segment::parse(&pattern[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if in_param {
+        return Err("unterminated parameter".to_string());
+    }
+
+    segments.push(Segment::parse(&pattern[start..])?);
+    Ok(segments)
+}
+
+/// Matches a request host against a parsed host pattern.
+///
+/// # Arguments
+///
+/// * `pattern`: The parsed host pattern (see `parse_host`).
+/// * `host`: The request host, lowercase and without a port.
+///
+/// # Returns
+///
+/// The captured label parameters if the host matches, else `None`.
+pub(crate) fn match_host(pattern: &[Segment], host: &str) -> Option<Vec<(String, String)>> {
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.len() != pattern.len() {
+        return None;
+    }
+
+    let mut captures = Vec::new();
+    for (segment, label) in pattern.iter().zip(labels) {
+        match segment {
+            Segment::Static(literal) if literal == label => {}
+            Segment::Param(name, constraint) if !label.is_empty() && constraint.matches(label) => {
+                captures.push((name.clone(), label.to_string()));
+            }
+            _ => return None,
+        }
+    }
+    Some(captures)
+}
+
+/// Splits a request path on `/`, dropping the leading empty segment.
+pub(crate) fn split_path(path: &str) -> Vec<&str> {
+    path.strip_prefix('/').unwrap_or(path).split('/').collect()
+}
+
+/// Normalizes a request path before routing.
+///
+/// Duplicate slashes and `.` segments are removed and `..` segments are resolved (never
+/// above the root), e.g. `/a//b/./c/../d` becomes `/a/b/d`. A trailing slash is kept.
+///
+/// # Arguments
+///
+/// * `path`: The raw request path.
+///
+/// # Returns
+///
+/// The normalized path, always starting with `/`.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let trailing = matches!(path.rsplit('/').next(), Some("" | "." | ".."));
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing && !segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Returns the path with its trailing slash added or removed, or `None` for the root.
+pub(crate) fn toggle_trailing_slash(path: &str) -> Option<String> {
+    match path {
+        "/" => None,
+        _ => match path.strip_suffix('/') {
+            Some(stripped) => Some(stripped.to_string()),
+            None => Some(format!("{}/", path)),
+        },
+    }
+}
+
+/// Percent-decodes a captured path value as UTF-8.
+///
+/// # Returns
+///
+/// The decoded value, or `None` if a `%` is not followed by two hex digits or the decoded
+/// bytes are not valid UTF-8.
+pub(crate) fn decode(raw: &str) -> Option<Cow<'_, str>> {
+    let bytes = raw.as_bytes();
+    let well_formed = bytes.iter().enumerate().all(|(i, &byte)| {
+        byte != b'%'
+            || bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+    });
+    if !well_formed {
+        return None;
+    }
+    percent_decode_str(raw).decode_utf8().ok()
+}
+
+/// Checks a decoded wildcard capture: it must be non-empty and must not contain a `..`
+/// component (which would escape the captured prefix).
+pub(crate) fn is_valid_tail(decoded: &str) -> bool {
+    !decoded.is_empty()
+        && !decoded
+            .split(['/', '\\'])
+            .any(|component| component == "..")
+}
+
+/// How requests differing from a route only by a trailing slash are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TrailingSlash {
+    /// `/users` and `/users/` are different paths (default).
+    #[default]
+    Strict,
+    /// Redirect with `308 Permanent Redirect` to the canonical path of the matching route.
+    /// Paths with duplicate slashes or dot segments are redirected to their normalized form.
+    Redirect,
+    /// Serve the matching route whether or not the request has a trailing slash.
+    MatchBoth,
+}
+
+/// Characters percent-encoded in generated path segments.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Builds a URL path from a route path pattern by filling in its parameters.
+///
+/// # Arguments
+///
+/// * `pattern`: The route path pattern (e.g. `/users/<id:int>/edit`).
+/// * `params`: Parameter names and values; values are percent-encoded.
+///
+/// # Returns
+///
+/// The URL path, or `None` if a parameter is missing, does not satisfy its constraint,
+/// or a wildcard value contains a `..` component.
+pub(crate) fn build_url(pattern: &str, params: &[(&str, &str)]) -> Option<String> {
+    let value_of = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    };
+
+    let mut url = String::new();
+
+    for segment in parse_pattern(pattern).ok()? {
+        url.push('/');
+        match segment {
+            Segment::Static(literal) => url.push_str(&literal),
+            Segment::Param(name, constraint) => {
+                let value = value_of(&name)?;
+                if value.is_empty() || !constraint.matches(value) {
+                    return None;
+                }
+                url.extend(utf8_percent_encode(value, PATH_SEGMENT));
+            }
+            Segment::Wildcard(name) => {
+                let value = value_of(&name)?.trim_start_matches('/');
+                if value.is_empty() || value.split('/').any(|component| component == "..") {
+                    return None;
+                }
+                let encoded: Vec<String> = value
+                    .split('/')
+                    .map(|component| utf8_percent_encode(component, PATH_SEGMENT).to_string())
+                    .collect();
+                url.push_str(&encoded.join("/"));
+            }
+        }
+    }
+
+    Some(url)
+}
+
+/// A node of the routing tree, keyed by static segments with parameter children.
+#[derive(Default)]
+struct Node {
+    statics: HashMap<String, Node>,  // Children keyed by literal segment
+    params: Vec<(Constraint, Node)>, // Parameter children, constrained ones first
+    wildcard: Vec<usize>,            // Routes capturing the rest of the path from here
+    routes: Vec<usize>,              // Routes ending at this node, in registration order
+}
+
+impl Node {
+    /// Returns the parameter child for a constraint, inserting it if needed.
+    ///
+    /// Constrained children are kept ahead of the unconstrained one so that, for example,
+    /// `/items/<id:int>` is tried before `/items/<name>` regardless of registration order.
+    fn param_child(&mut self, constraint: Constraint) -> &mut Node {
+        let index = match self
+            .params
+            .iter()
+            .position(|(c, _)| c.key() == constraint.key())
+        {
+            Some(index) => index,
+            None => {
+                let index = match constraint {
+                    Constraint::Any => self.params.len(),
+                    _ => self
+                        .params
+                        .iter()
+                        .position(|(c, _)| matches!(c, Constraint::Any))
+                        .unwrap_or(self.params.len()),
+                };
+                self.params.insert(index, (constraint, Node::default()));
+                index
+            }
+        };
+        &mut self.params[index].1
+    }
+}
+
+/// State accumulated while walking the tree for one lookup.
+#[derive(Default)]
+struct Search<'p> {
+    host: Option<&'p str>, // Request host, matched against host patterns
+    captures: Vec<(Cow<'p, str>, Cow<'p, str>)>, // Captured (raw, decoded) values
+    allowed: Vec<Method>,  // Methods of routes matching only the path
+    invalid: bool,         // A capture was not valid percent-encoded UTF-8
+}
+
+/// Per-route data the router needs once the tree is built.
+struct Entry {
+    method: Method,             // HTTP method of the route
+    host: Option<Vec<Segment>>, // Host pattern the request must match, if any
+    names: Vec<String>,         // Parameter names, in the order they are captured
+    auto_head: bool,            // A GET route also answers HEAD
+    auto_options: bool,         // OPTIONS is answered for the route's path
+}
+
+/// The result of looking up a request in the router.
+pub(crate) enum Lookup {
+    Found {
+        route: usize,                    // Index of the matched route
+        params: HashMap<String, String>, // Captured path parameters, percent-decoded
+        raw: HashMap<String, String>,    // Captured path parameters, as received
+    },
+    BadRequest, // A segment that could be captured is not valid percent-encoded UTF-8
+    Options(Vec<Method>), // An OPTIONS request answered with these methods
+    MethodNotAllowed(Vec<Method>), // The path matches, but only for these methods
+    NotFound,
+}
+
+/// Compiled prefix tree of routes, built once when the server starts.
+///
+/// Lookups walk the tree one segment at a time. Static segments take precedence over
+/// constrained parameters, which take precedence over unconstrained ones, which take
+/// precedence over wildcards. Among routes with the same shape the one registered first wins.
+pub(crate) struct Router {
+    root: Node,
+    entries: Vec<Entry>,
+}
+
+impl Router {
+    /// Builds the routing tree from the registered routes.
+    ///
+    /// # Arguments
+    ///
+    /// * `routes`: The routes to compile; their indices identify them in lookups.
+    ///
+    /// # Returns
+    ///
+    /// A router ready to serve lookups.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a route path or host is not a valid pattern.
+    pub(crate) fn new(routes: &[Route]) -> Self {
+        let mut root = Node::default();
+        let mut entries: Vec<Entry> = Vec::with_capacity(routes.len());
+
+        for (index, route) in routes.iter().enumerate() {
+            let mut node = &mut root;
+            let mut names = Vec::new();
+
+            let segments = parse_pattern(&route.path)
+                .unwrap_or_else(|e| panic!("Invalid route path '{}': {}", route.path, e));
+
+            let mut wildcard = false;
+
+            for segment in segments {
+                node = match segment {
+                    Segment::Static(literal) => node.statics.entry(literal).or_default(),
+                    Segment::Param(name, constraint) => {
+                        names.push(name);
+                        node.param_child(constraint)
+                    }
+                    Segment::Wildcard(name) => {
+                        names.push(name);
+                        wildcard = true;
+                        break; // Always the last segment
+                    }
+                };
+            }
+
+            let host = route.host.as_deref().map(|host| {
+                parse_host(host).unwrap_or_else(|e| panic!("Invalid route host '{}': {}", host, e))
+            });
+
+            // Host-restricted routes are tried before unrestricted ones at the same node
+            let candidates = if wildcard {
+                &mut node.wildcard
+            } else {
+                &mut node.routes
+            };
+            let position = match host {
+                Some(_) => candidates
+                    .iter()
+                    .position(|&other| entries[other].host.is_none())
+                    .unwrap_or(candidates.len()),
+                None => candidates.len(),
+            };
+            candidates.insert(position, index);
+
+            entries.push(Entry {
+                method: route.method.clone(),
+                host,
+                names,
+                auto_head: route.auto_head,
+                auto_options: route.auto_options,
+            });
+        }
+
+        Self { root, entries }
+    }
+
+    /// Finds the route matching the given method and path.
+    ///
+    /// # Arguments
+    ///
+    /// * `method`: The HTTP method of the request.
+    /// * `host`: The request host, lowercase and without a port, if known.
+    /// * `path`: The request path.
+    ///
+    /// # Returns
+    ///
+    /// The matched route index with its captured parameters (including those captured from
+    /// the host), `Lookup::BadRequest` if a
+    /// segment that could be captured is not valid percent-encoded UTF-8, `Lookup::Options` for OPTIONS
+    /// requests answered automatically, `Lookup::MethodNotAllowed` with the methods registered
+    /// for the path if none matches the method, or `Lookup::NotFound`.
+    ///
+    /// HEAD requests fall back to a GET route unless it opted out with `Route::auto_head`.
+    pub(crate) fn lookup(&self, method: &Method, host: Option<&str>, path: &str) -> Lookup {
+        let segments = split_path(path);
+        let mut search = Search {
+            host,
+            ..Search::default()
+        };
+
+        match self.find(&self.root, &segments, method, &mut search) {
+            Some(route) => {
+                let names = &self.entries[route].names;
+                let mut params = HashMap::new();
+                let mut raw = HashMap::new();
+                for (name, (raw_value, value)) in names.iter().zip(search.captures) {
+                    params.insert(name.clone(), value.into_owned());
+                    raw.insert(name.clone(), raw_value.into_owned());
+                }
+                if let (Some(pattern), Some(host)) = (&self.entries[route].host, host) {
+                    for (name, value) in match_host(pattern, host).unwrap_or_default() {
+                        raw.insert(name.clone(), value.clone());
+                        params.insert(name, value);
+                    }
+                }
+                Lookup::Found { route, params, raw }
+            }
+            None if search.invalid => Lookup::BadRequest,
+            None if *method == Method::OPTIONS && search.allowed.contains(&Method::OPTIONS) => {
+                Lookup::Options(search.allowed)
+            }
+            None if !search.allowed.is_empty() => Lookup::MethodNotAllowed(search.allowed),
+            None => Lookup::NotFound,
+        }
+    }
+
+    /// Recursively walks the tree, trying static children, then parameter children,
+    /// then wildcards. Captured values are percent-decoded before constraints are checked.
+    fn find<'p>(
+        &self,
+        node: &Node,
+        segments: &[&'p str],
+        method: &Method,
+        search: &mut Search<'p>,
+    ) -> Option<usize> {
+        let Some((segment, rest)) = segments.split_first() else {
+            return self.route_for(&node.routes, method, search.host, &mut search.allowed);
+        };
+
+        if let Some(child) = node.statics.get(*segment)
+            && let Some(route) = self.find(child, rest, method, search)
+        {
+            return Some(route);
+        }
+
+        if !node.params.is_empty() && !segment.is_empty() {
+            match decode(segment) {
+                Some(value) => {
+                    for (constraint, child) in &node.params {
+                        if !constraint.matches(&value) {
+                            continue;
+                        }
+                        search
+                            .captures
+                            .push((Cow::Borrowed(*segment), value.clone()));
+                        if let Some(route) = self.find(child, rest, method, search) {
+                            return Some(route);
+                        }
+                        search.captures.pop();
+                    }
+                }
+                None => search.invalid = true,
+            }
+        }
+
+        if !node.wildcard.is_empty() {
+            let tail = segments.join("/");
+            match decode(&tail) {
+                Some(value) if is_valid_tail(&value) => {
+                    let value = Cow::Owned(value.into_owned());
+                    if let Some(route) =
+                        self.route_for(&node.wildcard, method, search.host, &mut search.allowed)
+                    {
+                        search.captures.push((Cow::Owned(tail), value));
+                        return Some(route);
+                    }
+                }
+                Some(_) => {}
+                None => search.invalid = true,
+            }
+        }
+
+        None
+    }
+
+    /// Picks the first route registered for the method among the candidates whose host
+    /// pattern (if any) matches, recording the methods those candidates answer in `allowed`
+    /// when none matches.
+    fn route_for(
+        &self,
+        candidates: &[usize],
+        method: &Method,
+        host: Option<&str>,
+        allowed: &mut Vec<Method>,
+    ) -> Option<usize> {
+        let candidates: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&route| match (&self.entries[route].host, host) {
+                (None, _) => true,
+                (Some(pattern), Some(host)) => match_host(pattern, host).is_some(),
+                (Some(_), None) => false,
+            })
+            .collect();
+
+        let found = candidates
+            .iter()
+            .copied()
+            .find(|&route| self.entries[route].method == *method)
+            .or_else(|| match *method {
+                Method::HEAD => candidates.iter().copied().find(|&route| {
+                    self.entries[route].method == Method::GET && self.entries[route].auto_head
+                }),
+                _ => None,
+            });
+
+        if found.is_none() {
+            for &route in &candidates {
+                let entry = &self.entries[route];
+                let mut methods = vec![entry.method.clone()];
+                if entry.method == Method::GET && entry.auto_head {
+                    methods.push(Method::HEAD);
+                }
+                if entry.auto_options {
+                    methods.push(Method::OPTIONS);
+                }
+                for candidate in methods {
+                    if !allowed.contains(&candidate) {
+                        allowed.push(candidate);
+                    }
+                }
+            }
+        }
+
+        found
+    }
+}
+
+/// A problem detected between registered routes (see `Fluxor::check_routes`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouteConflict {
+    /// The same method and path are registered more than once; only the first is reachable.
+    Duplicate { method: Method, path: String },
+    /// A route has the same method and shape as an earlier one (e.g. `/a/<x>` and
+    /// `/a/<y>`) and can never match.
+    Shadowed {
+        method: Method,
+        path: String,
+        by: String, // Path of the earlier route that wins
+    },
+    /// Two routes share a name; `url_for` uses the first one.
+    DuplicateName {
+        name: String,
+        path: String,
+        first: String, // Path of the first route with that name
+    },
+}
+
+impl RouteConflict {
+    /// Whether the conflict makes a route unreachable (duplicate or shadowed routes), as
+    /// opposed to only ambiguous naming. Errors are reported as `[ERROR]` by `Fluxor::run`.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            RouteConflict::Duplicate { .. } | RouteConflict::Shadowed { .. }
+        )
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteConflict::Duplicate { method, path } => write!(
+                f,
+                "{} {} is registered more than once; only the first registration is reachable",
+                method, path
+            ),
+            RouteConflict::Shadowed { method, path, by } => write!(
+                f,
+                "{} {} is shadowed by {} {} and can never match",
+                method, path, method, by
+            ),
+            RouteConflict::DuplicateName { name, path, first } => write!(
+                f,
+                "route name '{}' is used by both {} and {}; url_for uses {}",
+                name, first, path, first
+            ),
+        }
+    }
+}
+
+/// Returns a key describing which requests a pattern matches, ignoring parameter names.
+fn shape(pattern: &str) -> Option<String> {
+    let segments = parse_pattern(pattern).ok()?;
+    let keys: Vec<String> = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Static(literal) => literal.clone(),
+            Segment::Param(_, constraint) => format!("<:{}>", constraint.key()),
+            Segment::Wildcard(_) => "<..>".to_string(),
+        })
+        .collect();
+    Some(keys.join("/"))
+}
+
+/// Detects duplicate and shadowed routes and duplicate route names.
+///
+/// # Arguments
+///
+/// * `routes`: The registered routes, in registration order.
+///
+/// # Returns
+///
+/// The conflicts found, in registration order of the offending routes.
+pub(crate) fn find_conflicts(routes: &[Route]) -> Vec<RouteConflict> {
+    let mut conflicts = Vec::new();
+    let mut shapes: HashMap<(Method, Option<String>, String), &str> = HashMap::new();
+    let mut names: HashMap<&str, &str> = HashMap::new();
+
+    for route in routes {
+        if let Some(key) = shape(&route.path) {
+            let host = route.host.as_ref().map(|host| host.to_ascii_lowercase());
+            match shapes.get(&(route.method.clone(), host.clone(), key.clone())) {
+                Some(first) if *first == route.path => conflicts.push(RouteConflict::Duplicate {
+                    method: route.method.clone(),
+                    path: route.path.clone(),
+                }),
+                Some(first) => conflicts.push(RouteConflict::Shadowed {
+                    method: route.method.clone(),
+                    path: route.path.clone(),
+                    by: first.to_string(),
+                }),
+                None => {
+                    shapes.insert((route.method.clone(), host, key), &route.path);
+                }
+            }
+        }
+
+        if let Some(name) = &route.name {
+            match names.get(name.as_str()) {
+                Some(first) => conflicts.push(RouteConflict::DuplicateName {
+                    name: name.clone(),
+                    path: route.path.clone(),
+                    first: first.to_string(),
+                }),
+                None => {
+                    names.insert(name, &route.path);
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::boxed;
+    use fluxio::{Body, Response};
+    use std::sync::Arc;
+
+    /// Builds a route the way `Fluxor::route` does, with a handler that is never called.
+    fn route(method: Method, path: &str) -> Route {
+        Route {
+            method,
+            path: path.to_string(),
+            handler: Arc::new(|_, _| boxed(async { Ok(Response::new(Body::empty())) })),
+            auto_head: true,
+            auto_options: true,
+            name: None,
+            host: None,
+            middleware: Vec::new(),
+            timeout: None,
+            body_limit: None,
+        }
+    }
+
+    /// Looks up a path and returns the matched route index with its decoded parameters.
+    fn found(router: &Router, method: Method, path: &str) -> (usize, HashMap<String, String>) {
+        match router.lookup(&method, None, path) {
+            Lookup::Found { route, params, .. } => (route, params),
+            _ => panic!("no route found for {} {}", method, path),
+        }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn static_segments_win_and_backtrack_to_parameters() {
+        let router = Router::new(&[route(Method::GET, "/a/b/c"), route(Method::GET, "/a/<x>/d")]);
+
+        assert_eq!(found(&router, Method::GET, "/a/b/c"), (0, HashMap::new()));
+        assert_eq!(
+            found(&router, Method::GET, "/a/b/d"),
+            (1, params(&[("x", "b")]))
+        );
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/a/b/e"),
+            Lookup::NotFound
+        ));
+    }
+
+    #[test]
+    fn constrained_parameters_are_tried_first() {
+        let router = Router::new(&[
+            route(Method::GET, "/items/<name>"),
+            route(Method::GET, "/items/<id:int>"),
+        ]);
+
+        assert_eq!(
+            found(&router, Method::GET, "/items/42"),
+            (1, params(&[("id", "42")]))
+        );
+        assert_eq!(
+            found(&router, Method::GET, "/items/hat"),
+            (0, params(&[("name", "hat")]))
+        );
+    }
+
+    #[test]
+    fn parameters_are_percent_decoded() {
+        let router = Router::new(&[route(Method::GET, "/s/<q>")]);
+
+        match router.lookup(&Method::GET, None, "/s/a%20b") {
+            Lookup::Found { params, raw, .. } => {
+                assert_eq!(params["q"], "a b");
+                assert_eq!(raw["q"], "a%20b");
+            }
+            _ => panic!("no route found"),
+        }
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/s/%FF"),
+            Lookup::BadRequest
+        ));
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/s/%zz"),
+            Lookup::BadRequest
+        ));
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/s/100%"),
+            Lookup::BadRequest
+        ));
+    }
+
+    #[test]
+    fn wildcards_capture_the_tail_without_parent_components() {
+        let router = Router::new(&[
+            route(Method::GET, "/files/<path..>"),
+            route(Method::GET, "/files/readme"),
+        ]);
+
+        assert_eq!(
+            found(&router, Method::GET, "/files/readme"),
+            (1, HashMap::new())
+        );
+        assert_eq!(
+            found(&router, Method::GET, "/files/docs/a%20b.txt"),
+            (0, params(&[("path", "docs/a b.txt")]))
+        );
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/files/docs/../secret"),
+            Lookup::NotFound
+        ));
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/files/%2e%2e/secret"),
+            Lookup::NotFound
+        ));
+        assert!(matches!(
+            router.lookup(&Method::GET, None, "/files"),
+            Lookup::NotFound
+        ));
+    }
+
+    #[test]
+    fn host_routes_are_tried_before_unrestricted_ones() {
+        let mut tenant = route(Method::GET, "/");
+        tenant.host("<tenant>.example.com");
+        let router = Router::new(&[route(Method::GET, "/"), tenant]);
+
+        match router.lookup(&Method::GET, Some("acme.example.com"), "/") {
+            Lookup::Found { route, params, .. } => {
+                assert_eq!(route, 1);
+                assert_eq!(params["tenant"], "acme");
+            }
+            _ => panic!("no route found"),
+        }
+        match router.lookup(&Method::GET, Some("example.org"), "/") {
+            Lookup::Found { route, .. } => assert_eq!(route, 0),
+            _ => panic!("no route found"),
+        }
+        match router.lookup(&Method::GET, None, "/") {
+            Lookup::Found { route, .. } => assert_eq!(route, 0),
+            _ => panic!("no route found"),
+        }
+    }
+
+    #[test]
+    fn allowed_methods_accumulate_across_matching_routes() {
+        let mut post = route(Method::POST, "/users/<id>");
+        post.auto_options = false;
+        let router = Router::new(&[
+            route(Method::GET, "/users/<id:int>"),
+            post,
+            route(Method::DELETE, "/users/<name>"),
+        ]);
+
+        match router.lookup(&Method::PUT, None, "/users/7") {
+            Lookup::MethodNotAllowed(allowed) => assert_eq!(
+                allowed,
+                [
+                    Method::GET,
+                    Method::HEAD,
+                    Method::OPTIONS,
+                    Method::POST,
+                    Method::DELETE
+                ]
+            ),
+            _ => panic!("expected 405"),
+        }
+        match router.lookup(&Method::OPTIONS, None, "/users/bob") {
+            Lookup::Options(allowed) => {
+                assert_eq!(allowed, [Method::POST, Method::DELETE, Method::OPTIONS])
+            }
+            _ => panic!("expected an OPTIONS answer"),
+        }
+        assert_eq!(found(&router, Method::HEAD, "/users/7").0, 0);
+        assert_eq!(found(&router, Method::POST, "/users/7").0, 1);
+    }
+
+    #[test]
+    fn conflicts_are_detected() {
+        let mut first = route(Method::GET, "/users/<id>");
+        first.name = Some("user".to_string());
+        let mut renamed = route(Method::GET, "/people/<id>");
+        renamed.name = Some("user".to_string());
+        let routes = [
+            first,
+            route(Method::GET, "/users/<id>"),
+            route(Method::GET, "/users/<name>"),
+            route(Method::POST, "/users/<name>"),
+            route(Method::GET, "/a/<x:int>"),
+            route(Method::GET, "/a/<y>"),
+            renamed,
+        ];
+
+        let conflicts = find_conflicts(&routes);
+        assert_eq!(
+            conflicts,
+            [
+                RouteConflict::Duplicate {
+                    method: Method::GET,
+                    path: "/users/<id>".to_string(),
+                },
+                RouteConflict::Shadowed {
+                    method: Method::GET,
+                    path: "/users/<name>".to_string(),
+                    by: "/users/<id>".to_string(),
+                },
+                RouteConflict::DuplicateName {
+                    name: "user".to_string(),
+                    path: "/people/<id>".to_string(),
+                    first: "/users/<id>".to_string(),
+                },
+            ]
+        );
+        assert!(conflicts[0].is_error());
+        assert!(conflicts[1].is_error());
+        assert!(!conflicts[2].is_error());
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize_path("/a//b/./c/../d"), "/a/b/d");
+        assert_eq!(normalize_path("/a/b/"), "/a/b/");
+        assert_eq!(normalize_path("/../../etc"), "/etc");
+        assert_eq!(normalize_path("/a/.."), "/");
+        assert_eq!(normalize_path(""), "/");
+    }
+
+    #[test]
+    fn trailing_slash_is_toggled() {
+        assert_eq!(toggle_trailing_slash("/users").as_deref(), Some("/users/"));
+        assert_eq!(toggle_trailing_slash("/users/").as_deref(), Some("/users"));
+        assert_eq!(toggle_trailing_slash("/"), None);
+    }
+
+    #[test]
+    fn urls_are_built_from_patterns() {
+        assert_eq!(
+            build_url("/users/<id:int>/edit", &[("id", "7")]).as_deref(),
+            Some("/users/7/edit")
+        );
+        assert_eq!(
+            build_url("/s/<q>", &[("q", "a b/c")]).as_deref(),
+            Some("/s/a%20b%2Fc")
+        );
+        assert_eq!(
+            build_url("/files/<path..>", &[("path", "docs/a b.txt")]).as_deref(),
+            Some("/files/docs/a%20b.txt")
+        );
+        assert_eq!(build_url("/users/<id:int>", &[("id", "bob")]), None);
+        assert_eq!(build_url("/users/<id>", &[]), None);
+        assert_eq!(build_url("/files/<path..>", &[("path", "../etc")]), None);
+    }
+}
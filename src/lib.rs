@@ -96,9 +96,9 @@
 //!       
 //! #[tokio::main]
 //! async fn main() {
-//!     let mut app = Fluxor::new();			// Initialize the application.
-//!     app.route(GET, "/", hello);				// Set the route (method, path, handler).
-//!     app.run("127.0.0.1", "8080").await;		// Start the HTTP server (host, port).
+//!     let mut app = Fluxor::new();            // Initialize the application.
+//!     app.route(GET, "/", hello);             // Set the route (method, path, handler).
+//!     app.run("127.0.0.1", "8080").await;     // Start the HTTP server (host, port).
 //! }
 //!       
 //! ```
@@ -124,10 +124,10 @@
 //! 
 //! #[tokio::main]
 //! async fn main() {
-//!     let mut app = Fluxor::new();							// Initialize the application.
-//!     app.route(GET, "/", hello);								// Set the route (method, path, handler).
-//!     app.route(GET, "/http-client", serve_http_client);  	// A simple http client to test your application.
-//!     app.run("127.0.0.1", "8080").await;						// Start the HTTP server (host, port).
+//!     let mut app = Fluxor::new();                            // Initialize the application.
+//!     app.route(GET, "/", hello);                             // Set the route (method, path, handler).
+//!     app.route(GET, "/http-client", serve_http_client);      // A simple http client to test your application.
+//!     app.run("127.0.0.1", "8080").await;                     // Start the HTTP server (host, port).
 //! }
 //!     
 //! ```
@@ -151,10 +151,10 @@
 //! 
 //! #[tokio::main]
 //! async fn main() {
-//!     let mut server = Fluxor::new();                     	// Initialize the application.
-//!     server.route(POST, "/", hello);                     	// Set the route (method, path, handler).
-//!     server.route(GET, "/http-client", serve_http_client);  	// A simple HTTP client to test your application.
-//!     server.run("127.0.0.1", "8080").await;              	// Start the HTTP server (host, port).
+//!     let mut server = Fluxor::new();                         // Initialize the application.
+//!     server.route(POST, "/", hello);                         // Set the route (method, path, handler).
+//!     server.route(GET, "/http-client", serve_http_client);   // A simple HTTP client to test your application.
+//!     server.run("127.0.0.1", "8080").await;                  // Start the HTTP server (host, port).
 //! }
 //! ```
 //! 